@@ -439,11 +439,19 @@ fn write_normal_text(ctx: &mut Builder, pos: Point, text: TextItemView) {
 
     *ctx.resources.languages.entry(text.item.lang).or_insert(0) += text.glyph_range.len();
 
+    // When a character is decomposed into multiple glyphs, only the first one
+    // maps back to it, so that the character isn't duplicated when the text
+    // is extracted.
     let glyph_set = ctx.resources.glyph_sets.entry(text.item.font.clone()).or_default();
+    let mut prev = None;
     for g in text.glyphs() {
-        let t = text.text();
-        let segment = &t[g.range()];
-        glyph_set.entry(g.id).or_insert_with(|| segment.into());
+        let range = g.range();
+        if range.is_empty() || prev.as_ref() != Some(&range) {
+            let t = text.text();
+            let segment = &t[range.clone()];
+            glyph_set.entry(g.id).or_insert_with(|| segment.into());
+        }
+        prev = Some(range);
     }
 
     let fill_transform = ctx.state.transforms(Size::zero(), pos);
@@ -541,6 +549,7 @@ fn write_color_glyphs(ctx: &mut Builder, pos: Point, text: TextItemView) {
     let y = pos.y.to_f32();
 
     let mut last_font = None;
+    let mut prev = None;
 
     ctx.content.begin_text();
     ctx.content.set_text_matrix([1.0, 0.0, 0.0, -1.0, x, y]);
@@ -572,9 +581,15 @@ fn write_color_glyphs(ctx: &mut Builder, pos: Point, text: TextItemView) {
 
         ctx.content.show(Str(&[index]));
 
-        glyph_set
-            .entry(glyph.id)
-            .or_insert_with(|| text.text()[glyph.range()].into());
+        // Like for normal text, only the first glyph of a decomposed
+        // character maps back to it.
+        let range = glyph.range();
+        if range.is_empty() || prev.as_ref() != Some(&range) {
+            glyph_set
+                .entry(glyph.id)
+                .or_insert_with(|| text.text()[range.clone()].into());
+        }
+        prev = Some(range);
     }
     ctx.content.end_text();
 }
@@ -4,13 +4,13 @@ use smallvec::SmallVec;
 use crate::diag::{bail, SourceResult};
 use crate::engine::Engine;
 use crate::foundations::{
-    cast, elem, Args, AutoValue, Construct, Content, NativeElement, Packed, Resolve,
-    Smart, StyleChain, Value,
+    cast, elem, Args, AutoValue, Cast, Construct, Content, NativeElement, Packed,
+    Resolve, Smart, StyleChain, Value,
 };
 use crate::introspection::Locator;
 use crate::layout::{
     Abs, Axes, Corners, Em, Fr, Fragment, Frame, FrameKind, Length, Region, Regions, Rel,
-    Sides, Size, Spacing,
+    Sides, Size, Spacing, VAlignment,
 };
 use crate::utils::Numeric;
 use crate::visualize::{clip_rect, Paint, Stroke};
@@ -58,6 +58,35 @@ pub struct BoxElem {
     #[resolve]
     pub baseline: Rel<Length>,
 
+    /// How to align the box vertically within its line.
+    ///
+    /// When `{auto}`, the box's baseline is aligned with the line's baseline.
+    /// When set to `{top}`, `{horizon}`, or `{bottom}`, the box is instead
+    /// aligned relative to the top and bottom edges of the line, which grows
+    /// as necessary to fit the box.
+    ///
+    /// ```example
+    /// Top #box(valign: top, rect(height: 2em))
+    /// and bottom #box(valign: bottom, rect(height: 2em)).
+    /// ```
+    pub valign: Smart<VAlignment>,
+
+    /// How the box affects the height of its line.
+    ///
+    /// By default, a line grows to fit a tall box, which disturbs the regular
+    /// spacing between lines. With `{"overlap"}`, the box keeps its size but
+    /// doesn't make the line taller, overlapping the leading and lines around
+    /// it instead. With `{"clip"}`, the box is additionally cut off at the top
+    /// and bottom edges of the line.
+    ///
+    /// ```example
+    /// #set par(leading: 1em)
+    /// A line with a
+    /// #box(line-overflow: "overlap", rect(height: 2em)) box that doesn't
+    /// push the lines around it apart.
+    /// ```
+    pub line_overflow: LineOverflow,
+
     /// The box's background color. See the
     /// [rectangle's documentation]($rect.fill) for more details.
     pub fill: Option<Paint>,
@@ -780,6 +809,26 @@ cast! {
     v: Content => Self::Content(v),
 }
 
+/// How an inline box affects the height of its line.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Cast)]
+pub enum LineOverflow {
+    /// The line grows to fit the box.
+    #[default]
+    Grow,
+    /// The box doesn't contribute to the line's height and may overlap the
+    /// surrounding leading and lines.
+    Overlap,
+    /// Like `overlap`, but the box is clipped to the line's extent.
+    Clip,
+}
+
+impl LineOverflow {
+    /// Whether the box contributes to the line's height.
+    pub fn is_grow(self) -> bool {
+        matches!(self, Self::Grow)
+    }
+}
+
 /// Defines how to size something along an axis.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Sizing {
@@ -6,20 +6,27 @@
 
 use std::fmt::{self, Debug, Formatter};
 
+use comemo::Track;
+
 use crate::diag::{bail, SourceResult};
 use crate::engine::Engine;
 use crate::foundations::{
-    elem, Args, Construct, Content, NativeElement, Packed, Resolve, Smart, StyleChain,
+    elem, Args, Construct, Content, Context, NativeElement, Packed, Resolve, Smart,
+    StyleChain,
 };
 use crate::introspection::{Locator, SplitLocator, Tag, TagElem};
 use crate::layout::{
     Abs, AlignElem, Axes, BlockElem, ColbreakElem, FixedAlignment, FlushElem, Fr,
-    Fragment, Frame, FrameItem, PlaceElem, Point, Regions, Rel, Size, Spacing, VElem,
+    Fragment, Frame, FrameItem, LineFrames, PlaceElem, Point, Ratio, Regions, Rel, Size,
+    Spacing, VElem,
 };
-use crate::model::{FootnoteElem, FootnoteEntry, ParElem};
+use crate::model::{FootnoteElem, FootnoteEntry, Numbering, ParElem};
 use crate::realize::StyleVec;
 use crate::utils::Numeric;
 
+/// Tolerance when snapping baselines to a grid, in multiples of the grid step.
+const GRID_EPS: f64 = 1e-6;
+
 /// Arranges spacing, paragraphs and block-level elements into a flow.
 ///
 /// This element is responsible for layouting both the top-level content flow
@@ -93,6 +100,8 @@ struct FlowLayouter<'a, 'e> {
     footnote_config: FootnoteConfig,
     /// Finished frames for previous regions.
     finished: Vec<Frame>,
+    /// The number of the last numbered paragraph line.
+    line_number: usize,
 }
 
 /// Cached footnote configuration.
@@ -107,6 +116,9 @@ struct FootnoteConfig {
 enum FlowItem {
     /// Spacing between other items and whether it is weak.
     Absolute(Abs, bool),
+    /// Weak leading between the lines of a paragraph that may stretch by up
+    /// to the given ratio of itself to fill the region.
+    Leading(Abs, Ratio),
     /// Fractional spacing between other items.
     Fractional(Fr),
     /// A frame for a layouted block.
@@ -145,6 +157,11 @@ enum FlowItem {
 }
 
 impl FlowItem {
+    /// Whether this item is weak spacing.
+    fn is_weak(&self) -> bool {
+        matches!(self, Self::Absolute(_, true) | Self::Leading(_, _))
+    }
+
     /// Whether this item is out-of-flow.
     ///
     /// Out-of-flow items are guaranteed to have a [zero size][Size::zero()].
@@ -204,6 +221,7 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
             expand,
             initial: regions.size,
             last_was_par: false,
+            line_number: 0,
             items: vec![],
             pending_tags: vec![],
             pending_floats: vec![],
@@ -277,27 +295,46 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
     ) -> SourceResult<()> {
         // Fetch properties.
         let align = AlignElem::alignment_in(styles).resolve(styles);
-        let leading = ParElem::leading_in(styles);
+        let sticky = ParElem::keep_with_next_in(styles);
+        let grid = ParElem::baseline_grid_in(styles).filter(|step| *step > Abs::zero());
+        let numbering = ParElem::line_numbering_in(styles).as_ref();
+        let stretch = ParElem::justify_vertical_in(styles);
 
         // Layout the paragraph into lines. This only depends on the base size,
         // not on the Y position.
         let consecutive = self.last_was_par;
         let locator = self.locator.next(&par.span());
-        let lines = par
-            .layout(
-                self.engine,
-                locator,
-                styles,
-                consecutive,
-                self.regions.base(),
-                self.regions.expand.x,
-            )?
-            .into_frames();
+        let LineFrames { frames: lines, leading, sticky: glued } = par.layout_lines(
+            self.engine,
+            locator,
+            styles,
+            consecutive,
+            self.regions.base(),
+            self.regions.expand.x,
+        )?;
 
-        // If the first line doesn’t fit in this region, then defer any
-        // previous sticky frame to the next region (if available)
-        if let Some(first) = lines.first() {
-            while !self.regions.size.y.fits(first.height()) && !self.regions.in_last() {
+        // Determine the height of each group of lines that must be kept in the
+        // same region, at the index of the group's first line.
+        let mut heights = vec![Abs::zero(); lines.len()];
+        let mut start = 0;
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                if glued[i - 1] {
+                    heights[start] += leading[i - 1];
+                } else {
+                    start = i;
+                }
+            }
+            heights[start] += line.height();
+        }
+
+        // If the first group of lines doesn’t fit in this region, then defer
+        // any previous sticky frame to the next region (if available)
+        if let Some(&height) = heights.first() {
+            while !self.regions.size.y.fits(height)
+                && self.regions.full.fits(height)
+                && !self.regions.in_last()
+            {
                 let in_last = self.finish_region_with_migration()?;
                 if in_last {
                     break;
@@ -308,22 +345,97 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
         // Layout the lines.
         for (i, mut frame) in lines.into_iter().enumerate() {
             if i > 0 {
-                self.handle_item(FlowItem::Absolute(leading, true))?;
+                let leading = leading[i - 1];
+                self.handle_item(match stretch {
+                    Some(ratio) => FlowItem::Leading(leading, ratio),
+                    None => FlowItem::Absolute(leading, true),
+                })?;
+
+                // Move a group of lines that must be kept together to the next
+                // region if it doesn't fit into this one, but would fit into
+                // an empty one.
+                let height = heights[i];
+                if !glued[i - 1]
+                    && !self.regions.size.y.fits(height)
+                    && self.regions.full.fits(height)
+                    && !self.regions.in_last()
+                {
+                    self.finish_region(false)?;
+                }
+            }
+
+            // Move the line down such that its baseline lands on the grid. If
+            // the line then doesn't fit anymore, it starts the next region,
+            // where we need to snap it anew.
+            if let Some(step) = grid {
+                let mut offset = self.grid_offset(step, &frame);
+                if !self.regions.size.y.fits(offset + frame.height())
+                    && !self.regions.in_last()
+                {
+                    self.finish_region(false)?;
+                    offset = self.grid_offset(step, &frame);
+                }
+
+                if !offset.is_zero() {
+                    self.handle_item(FlowItem::Absolute(offset, false))?;
+                }
+            }
+
+            if let Some(numbering) = numbering {
+                self.number_line(&mut frame, numbering, styles)?;
             }
 
             self.drain_tag(&mut frame);
-            self.handle_item(FlowItem::Frame {
-                frame,
-                align,
-                sticky: false,
-                movable: true,
-            })?;
+            self.handle_item(FlowItem::Frame { frame, align, sticky, movable: true })?;
         }
 
         self.last_was_par = true;
         Ok(())
     }
 
+    /// Count a paragraph line and, if it is due, attach its number next to it.
+    fn number_line(
+        &mut self,
+        frame: &mut Frame,
+        numbering: &Numbering,
+        styles: StyleChain,
+    ) -> SourceResult<()> {
+        self.line_number += 1;
+        if self.line_number % ParElem::line_numbering_every_in(styles).get() != 0 {
+            return Ok(());
+        }
+
+        let context = Context::new(None, Some(styles));
+        let number = numbering
+            .apply(self.engine, context.track(), &[self.line_number])?
+            .display()
+            .layout(
+                self.engine,
+                self.locator.next(&()),
+                styles,
+                Regions::one(self.regions.base(), Axes::splat(false)),
+            )?
+            .into_frame();
+
+        // Place the number in the margin, on the line's baseline.
+        let gap = ParElem::line_numbering_gap_in(styles);
+        let x = match ParElem::line_numbering_side_in(styles) {
+            FixedAlignment::End => frame.width() + gap,
+            _ => -gap - number.width(),
+        };
+        let y = frame.baseline() - number.baseline();
+        frame.push_frame(Point::new(x, y), number);
+        Ok(())
+    }
+
+    /// How far a line frame needs to be moved down such that its baseline
+    /// lands on a baseline grid with the given step.
+    fn grid_offset(&self, step: Abs, frame: &Frame) -> Abs {
+        let baseline = self.regions.full - self.regions.size.y + frame.baseline();
+        let snapped = step * (baseline / step - GRID_EPS).ceil();
+        (snapped - baseline).max(Abs::zero())
+    }
+
     /// Layout into multiple regions.
     fn handle_block(
         &mut self,
@@ -433,8 +545,8 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
     /// Layout a finished frame.
     fn handle_item(&mut self, mut item: FlowItem) -> SourceResult<()> {
         match item {
-            FlowItem::Absolute(v, weak) => {
-                if weak
+            FlowItem::Absolute(v, _) | FlowItem::Leading(v, _) => {
+                if item.is_weak()
                     && !self
                         .items
                         .iter()
@@ -543,7 +655,7 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
         let mut sticky = self.items.len();
         for (i, item) in self.items.iter().enumerate().rev() {
             match *item {
-                FlowItem::Absolute(_, _) => {}
+                FlowItem::Absolute(_, _) | FlowItem::Leading(_, _) => {}
                 FlowItem::Frame { sticky: true, .. } => sticky = i,
                 _ => break,
             }
@@ -578,16 +690,13 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
         }
 
         // Trim weak spacing.
-        while self
-            .items
-            .last()
-            .is_some_and(|item| matches!(item, FlowItem::Absolute(_, true)))
-        {
+        while self.items.last().is_some_and(FlowItem::is_weak) {
             self.items.pop();
         }
 
         // Determine the used size.
         let mut fr = Fr::zero();
+        let mut stretch = Abs::zero();
         let mut used = Size::zero();
         let mut footnote_height = Abs::zero();
         let mut float_top_height = Abs::zero();
@@ -596,6 +705,10 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
         for item in &self.items {
             match item {
                 FlowItem::Absolute(v, _) => used.y += *v,
+                FlowItem::Leading(v, ratio) => {
+                    used.y += *v;
+                    stretch += *v * ratio.get();
+                }
                 FlowItem::Fractional(v) => fr += *v,
                 FlowItem::Frame { frame, .. } => {
                     used.y += frame.height();
@@ -623,6 +736,18 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
         }
         used.y += footnote_height + float_top_height + float_bottom_height;
 
+        // Stretch the leading of vertically justified paragraphs to fill the
+        // region. We only do this if the region is finished because its
+        // content continues in the next one, which isn't forced.
+        let mut stretch_factor = 0.0;
+        if !force && fr.is_zero() && self.initial.y.is_finite() && !stretch.is_zero() {
+            let remaining = self.initial.y - used.y;
+            if remaining > Abs::zero() {
+                stretch_factor = (remaining / stretch).min(1.0);
+                used.y += stretch * stretch_factor;
+            }
+        }
+
         // Determine the size of the flow in this region depending on whether
         // the region expands. Also account for fractional spacing and
         // footnotes.
@@ -651,6 +776,9 @@ impl<'a, 'e> FlowLayouter<'a, 'e> {
                 FlowItem::Absolute(v, _) => {
                     offset += v;
                 }
+                FlowItem::Leading(v, ratio) => {
+                    offset += v + v * ratio.get() * stretch_factor;
+                }
                 FlowItem::Fractional(v) => {
                     let remaining = self.initial.y - used.y;
                     let length = v.share(fr, remaining);
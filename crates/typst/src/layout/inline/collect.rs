@@ -1,14 +1,16 @@
+use unicode_bidi::{bidi_class, BidiClass};
+
 use super::*;
 use crate::diag::bail;
-use crate::foundations::{Packed, Resolve};
+use crate::foundations::{Packed, Resolve, Smart, Styles};
 use crate::introspection::{Tag, TagElem};
 use crate::layout::{
-    Abs, AlignElem, BoxElem, Dir, Fr, Frame, HElem, InlineElem, InlineItem, Sizing,
-    Spacing,
+    Abs, AlignElem, BoxElem, Dir, Fr, Frame, HElem, InlineElem, InlineItem, LineOverflow,
+    Sizing, Spacing, VAlignment,
 };
 use crate::syntax::Span;
 use crate::text::{
-    LinebreakElem, SmartQuoteElem, SmartQuoter, SmartQuotes, SpaceElem, TextElem,
+    LinebreakElem, SmartQuoteElem, SmartQuoter, SmartQuotes, SpaceElem, TextDir, TextElem,
 };
 use crate::utils::Numeric;
 
@@ -33,10 +35,15 @@ pub enum Item<'a> {
     Text(ShapedText<'a>),
     /// Absolute spacing between other items, and whether it is weak.
     Absolute(Abs, bool),
+    /// Absolute spacing between other items, how much it may grow when the
+    /// line is justified, and whether it is weak.
+    Stretchable(Abs, Abs, bool),
     /// Fractional spacing between other items.
     Fractional(Fr, Option<(&'a Packed<BoxElem>, Locator<'a>, StyleChain<'a>)>),
-    /// Layouted inline-level content.
-    Frame(Frame, StyleChain<'a>),
+    /// Layouted inline-level content, how to align it vertically within the
+    /// line (`auto` for baseline alignment) and how it affects the line's
+    /// height.
+    Frame(Frame, StyleChain<'a>, Smart<VAlignment>, LineOverflow),
     /// A tag.
     Tag(&'a Tag),
     /// An item that is invisible and needs to be skipped, e.g. a Unicode
@@ -66,8 +73,10 @@ impl<'a> Item<'a> {
     pub fn textual(&self) -> &str {
         match self {
             Self::Text(shaped) => shaped.text,
-            Self::Absolute(_, _) | Self::Fractional(_, _) => SPACING_REPLACE,
-            Self::Frame(_, _) => OBJ_REPLACE,
+            Self::Absolute(..) | Self::Stretchable(..) | Self::Fractional(..) => {
+                SPACING_REPLACE
+            }
+            Self::Frame(..) => OBJ_REPLACE,
             Self::Tag(_) => "",
             Self::Skip(s) => s,
         }
@@ -82,12 +91,18 @@ impl<'a> Item<'a> {
     pub fn natural_width(&self) -> Abs {
         match self {
             Self::Text(shaped) => shaped.width,
-            Self::Absolute(v, _) => *v,
-            Self::Frame(frame, _) => frame.width(),
+            Self::Absolute(v, _) | Self::Stretchable(v, ..) => *v,
+            Self::Frame(frame, ..) => frame.width(),
             Self::Fractional(_, _) | Self::Tag(_) => Abs::zero(),
             Self::Skip(_) => Abs::zero(),
         }
     }
+
+    /// Whether this is weak spacing, which is trimmed at the start and end of
+    /// a line.
+    pub fn is_weak(&self) -> bool {
+        matches!(self, Self::Absolute(_, true) | Self::Stretchable(_, _, true))
+    }
 }
 
 /// An item or not-yet shaped text. We can't shape text until we have collected
@@ -153,6 +168,7 @@ pub fn collect<'a>(
             collector.push_text(" ", styles);
         } else if let Some(elem) = child.to_packed::<TextElem>() {
             collector.build_text(styles, |full| {
+                let prev = full.chars().next_back();
                 let dir = TextElem::dir_in(styles);
                 if dir != outer_dir {
                     // Insert "Explicit Directional Embedding".
@@ -164,7 +180,8 @@ pub fn collect<'a>(
                 }
 
                 if let Some(case) = TextElem::case_in(styles) {
-                    full.push_str(&case.apply(elem.text()));
+                    let lang = Some(TextElem::lang_in(styles));
+                    full.push_str(&case.apply(elem.text(), lang, prev));
                 } else {
                     full.push_str(elem.text());
                 }
@@ -176,16 +193,22 @@ pub fn collect<'a>(
             });
         } else if let Some(elem) = child.to_packed::<HElem>() {
             let amount = elem.amount();
-            if amount.is_zero() {
+            let stretch = elem.stretch(styles);
+            if amount.is_zero() && stretch.is_zero() {
                 continue;
             }
 
             collector.push_item(match amount {
                 Spacing::Fr(fr) => Item::Fractional(*fr, None),
-                Spacing::Rel(rel) => Item::Absolute(
-                    rel.resolve(styles).relative_to(region.x),
-                    elem.weak(styles),
-                ),
+                Spacing::Rel(rel) => {
+                    let amount = rel.resolve(styles).relative_to(region.x);
+                    let weak = elem.weak(styles);
+                    if stretch > Abs::zero() {
+                        Item::Stretchable(amount, stretch, weak)
+                    } else {
+                        Item::Absolute(amount, weak)
+                    }
+                }
             });
         } else if let Some(elem) = child.to_packed::<LinebreakElem>() {
             collector
@@ -231,7 +254,12 @@ pub fn collect<'a>(
                         collector.push_item(Item::Absolute(space, weak));
                     }
                     InlineItem::Frame(frame) => {
-                        collector.push_item(Item::Frame(frame, styles));
+                        collector.push_item(Item::Frame(
+                            frame,
+                            styles,
+                            Smart::Auto,
+                            LineOverflow::Grow,
+                        ));
                     }
                 }
             }
@@ -242,8 +270,26 @@ pub fn collect<'a>(
             if let Sizing::Fr(v) = elem.width(styles) {
                 collector.push_item(Item::Fractional(v, Some((elem, loc, styles))));
             } else {
-                let frame = elem.layout(engine, loc, styles, region)?;
-                collector.push_item(Item::Frame(frame, styles));
+                // Unless the box has a direction of its own, lay it out in the
+                // direction of the text around it.
+                let mut dir = TextElem::dir_in(styles);
+                if dir == outer_dir {
+                    dir = inline_dir(&collector.full, outer_dir);
+                }
+
+                let local = if dir != TextElem::dir_in(styles) {
+                    Styles::from(TextElem::set_dir(TextDir(Smart::Custom(dir))))
+                } else {
+                    Styles::default()
+                };
+
+                let frame = elem.layout(engine, loc, styles.chain(&local), region)?;
+                collector.push_item(Item::Frame(
+                    frame,
+                    styles,
+                    elem.valign(styles),
+                    elem.line_overflow(styles),
+                ));
             }
         } else if let Some(elem) = child.to_packed::<TagElem>() {
             collector.push_item(Item::Tag(&elem.tag));
@@ -258,6 +304,43 @@ pub fn collect<'a>(
     Ok((collector.full, collector.segments, collector.spans))
 }
 
+/// Determines the direction of inline content that follows the given text.
+///
+/// Like the object replacement character standing in for it, the content
+/// takes on the direction of the closest preceding strong character. Closed
+/// isolates are skipped as a whole, while a closed embedding or one the content
+/// is part of determines the direction itself. If there is no strong character,
+/// the content takes the paragraph's base direction.
+fn inline_dir(full: &str, base: Dir) -> Dir {
+    // The number of closed isolates and embeddings we are currently in while
+    // going backwards.
+    let mut isolates = 0;
+    let mut embeddings = 0;
+    for c in full.chars().rev() {
+        match bidi_class(c) {
+            BidiClass::PDI => isolates += 1,
+            BidiClass::LRI | BidiClass::RLI | BidiClass::FSI if isolates > 0 => {
+                isolates -= 1;
+            }
+            _ if isolates > 0 => {}
+            BidiClass::PDF => embeddings += 1,
+            BidiClass::LRE | BidiClass::RLE | BidiClass::LRO | BidiClass::RLO
+                if embeddings > 1 =>
+            {
+                embeddings -= 1;
+            }
+            BidiClass::L if embeddings == 0 => return Dir::LTR,
+            BidiClass::R | BidiClass::AL if embeddings == 0 => return Dir::RTL,
+            BidiClass::LRE | BidiClass::LRO | BidiClass::LRI | BidiClass::FSI => {
+                return Dir::LTR
+            }
+            BidiClass::RLE | BidiClass::RLO | BidiClass::RLI => return Dir::RTL,
+            _ => {}
+        }
+    }
+    base
+}
+
 /// Collects segments.
 struct Collector<'a> {
     full: String,
@@ -1,8 +1,18 @@
 use super::*;
+use crate::foundations::{NativeElement, Smart};
 use crate::layout::{Abs, Frame, Point};
+use crate::model::Overflow;
+use crate::text::TextElem;
 use crate::utils::Numeric;
+use crate::visualize::Path;
+
+/// The number of lines from which on their frames are built in parallel.
+const PARALLEL_LINES: usize = 32;
 
 /// Turns the selected lines into frames.
+///
+/// Also determines the leading between each pair of consecutive frames and
+/// which lines must be kept in the same region.
 #[typst_macros::time]
 pub fn finalize(
     engine: &mut Engine,
@@ -11,47 +21,165 @@ pub fn finalize(
     styles: StyleChain,
     region: Size,
     expand: bool,
-) -> SourceResult<Fragment> {
-    // Determine the paragraph's width: Full width of the region if we should
-    // expand or there's fractional spacing, fit-to-width otherwise.
-    let width = if !region.x.is_finite()
-        || (!expand && lines.iter().all(|line| line.fr().is_zero()))
-    {
-        region
-            .x
-            .min(p.hang + lines.iter().map(|line| line.width).max().unwrap_or_default())
-    } else {
-        region.x
+) -> SourceResult<LineFrames> {
+    let narrowed = narrow(p, region);
+    let width = match narrowed {
+        Some((inner, _)) => inner.x,
+        None => paragraph_width(p, lines, region, expand),
     };
 
-    // Stack the lines into one frame per region.
+    // Stack the lines into one frame per region. Building the frames of the
+    // lines is independent, so it is worth doing in parallel for long
+    // paragraphs.
     let shrink = ParElem::shrink_in(styles);
-    let mut frames: Vec<Frame> = lines
-        .iter()
-        .map(|line| commit(engine, p, line, width, region.y, shrink))
-        .collect::<SourceResult<_>>()?;
-
-    // Positive ratios enable prevention, while zero and negative ratios disable
-    // it.
-    if p.costs.orphan().get() > 0.0 {
-        // Prevent orphans.
-        if frames.len() >= 2 && !frames[1].is_empty() {
-            let second = frames.remove(1);
-            let first = &mut frames[0];
-            merge(first, second, p.leading);
+    let mut frames: Vec<Frame> = if lines.len() >= PARALLEL_LINES {
+        engine
+            .parallelize(lines, |engine, line| {
+                commit(engine, p, line, width, region.y, shrink)
+            })
+            .collect::<SourceResult<_>>()?
+    } else {
+        lines
+            .iter()
+            .map(|line| commit(engine, p, line, width, region.y, shrink))
+            .collect::<SourceResult<_>>()?
+    };
+
+    // Set the first line with additional styles, if requested.
+    if let Some(first) = lines.first() {
+        if let Some(frame) = with_first_line(engine, p, first, styles, |engine, line| {
+            commit(engine, p, line, width, region.y, shrink)
+        })? {
+            frames[0] = frame?;
         }
     }
-    if p.costs.widow().get() > 0.0 {
-        // Prevent widows.
-        let len = frames.len();
-        if len >= 2 && !frames[len - 2].is_empty() {
-            let second = frames.pop().unwrap();
-            let first = frames.last_mut().unwrap();
-            merge(first, second, p.leading);
+
+    // Move the first line's baseline to the requested distance from the top.
+    if let (Smart::Custom(distance), Some(first)) =
+        (ParElem::first_baseline_in(styles), frames.first_mut())
+    {
+        let delta = distance - first.baseline();
+        let height = first.height() + delta;
+        first.translate(Point::with_y(delta));
+        first.size_mut().y = height.max(Abs::zero());
+    }
+
+    // Cut off the lines at the paragraph's edges, if requested. Only the
+    // horizontal overflow is cut off: The clip path extends far above and
+    // below each line, so that its ascenders and descenders stay intact.
+    if p.overflow == Overflow::Clip {
+        for frame in &mut frames {
+            let size = frame.size();
+            let extent = size.x.max(size.y);
+            let mut path = Path::new();
+            path.move_to(Point::with_y(-extent));
+            path.line_to(Point::new(size.x, -extent));
+            path.line_to(Point::new(size.x, size.y + extent));
+            path.line_to(Point::with_y(size.y + extent));
+            path.close_path();
+            frame.clip(path);
         }
     }
 
-    Ok(Fragment::frames(frames))
+    // Place the lines of a narrowed paragraph within the full region.
+    if let Some((_, offset)) = narrowed {
+        let full = if expand && region.x.is_finite() {
+            region.x
+        } else {
+            width + offset.max(Abs::zero())
+        };
+        for frame in &mut frames {
+            frame.translate(Point::with_x(offset));
+            frame.size_mut().x = full;
+        }
+    }
+
+    // Determine the leading between the lines.
+    let mut leading: Vec<Abs> = frames
+        .iter()
+        .enumerate()
+        .map(|(i, frame)| p.leading_below(frame.height(), lines.get(i + 1)))
+        .collect();
+    leading.pop();
+
+    let sticky = stickiness(p, &frames, &leading, styles, region);
+    Ok(LineFrames { frames, leading, sticky })
+}
+
+/// Determine which lines must stay in the same region as the line after them.
+///
+/// This prevents orphans and widows by keeping the first and last few lines
+/// together, and keeps the whole paragraph together if requested and
+/// possible.
+fn stickiness(
+    p: &Preparation,
+    frames: &[Frame],
+    leading: &[Abs],
+    styles: StyleChain,
+    region: Size,
+) -> Vec<bool> {
+    let len = frames.len();
+    let mut sticky = vec![false; len.saturating_sub(1)];
+    if len == 0 {
+        return sticky;
+    }
+
+    // Positive ratios enable prevention, while zero and negative ratios
+    // disable it.
+    let orphans = if p.costs.orphan().get() > 0.0 {
+        let count = ParElem::orphans_in(styles).max(1);
+        1 + frames[1..]
+            .iter()
+            .take(count - 1)
+            .take_while(|f| !f.is_empty())
+            .count()
+    } else {
+        1
+    };
+    let widows = if p.costs.widow().get() > 0.0 {
+        let count = ParElem::widows_in(styles).max(1);
+        1 + frames[..len - 1]
+            .iter()
+            .rev()
+            .take(count - 1)
+            .take_while(|f| !f.is_empty())
+            .count()
+    } else {
+        1
+    };
+
+    // A paragraph that is taller than a full region can't be kept together,
+    // so it is split normally.
+    let height =
+        frames.iter().map(Frame::height).sum::<Abs>() + leading.iter().sum::<Abs>();
+    let together =
+        ParElem::keep_together_in(styles).applies(len) && region.y.fits(height);
+
+    if orphans + widows > len || together {
+        // The paragraph is too short to be split anywhere or shall be kept
+        // together as a whole.
+        sticky.fill(true);
+    } else {
+        sticky[..orphans - 1].fill(true);
+        sticky[len - widows..].fill(true);
+    }
+
+    sticky
+}
+
+impl LineFrames {
+    /// Merge the lines that must be kept together into one frame each.
+    pub fn merge(self) -> Fragment {
+        let mut frames = self.frames.into_iter();
+        let mut merged: Vec<Frame> = frames.next().into_iter().collect();
+        for ((frame, leading), sticky) in frames.zip(self.leading).zip(self.sticky) {
+            match merged.last_mut() {
+                Some(last) if sticky => merge(last, frame, leading),
+                _ => merged.push(frame),
+            }
+        }
+        Fragment::frames(merged)
+    }
 }
 
 /// Merge two line frames
@@ -61,3 +189,65 @@ fn merge(first: &mut Frame, second: Frame, leading: Abs) {
     first.push_frame(Point::with_y(offset), second);
     first.size_mut().y = total;
 }
+
+/// Sets the first line of a paragraph with the additional styles of
+/// [`first-line`](ParElem::first_line) and passes it to `f`.
+///
+/// Returns `None` without calling `f` if there are no such styles. The line
+/// keeps the range it was broken at, only its text is reshaped.
+pub fn with_first_line<T>(
+    engine: &mut Engine,
+    p: &Preparation,
+    first: &Line,
+    styles: StyleChain,
+    f: impl FnOnce(&mut Engine, &Line) -> T,
+) -> SourceResult<Option<T>> {
+    let Some(args) = ParElem::first_line_in(styles) else { return Ok(None) };
+    let local = TextElem::elem().set(engine, args)?;
+    let bases: Vec<StyleChain> = first
+        .items
+        .iter()
+        .filter_map(Item::text)
+        .map(|text| text.styles)
+        .collect();
+    let chains: Vec<StyleChain> = bases.iter().map(|base| base.chain(&local)).collect();
+    let restyled = restyle(engine, p, first, &chains);
+    Ok(Some(f(engine, &restyled)))
+}
+
+/// Narrow the region to the paragraph's own width, if it has one.
+///
+/// Also returns the horizontal offset of the narrowed region within the full
+/// one, which depends on the paragraph's alignment.
+pub fn narrow(p: &Preparation, region: Size) -> Option<(Size, Abs)> {
+    let width = p.width.custom()?.relative_to(region.x);
+    if !width.is_finite() {
+        return None;
+    }
+
+    let offset = if region.x.is_finite() {
+        p.align.position(region.x - width)
+    } else {
+        Abs::zero()
+    };
+
+    Some((Size::new(width, region.y), offset))
+}
+
+/// Determine the paragraph's width: Full width of the region if we should
+/// expand or there's fractional spacing, fit-to-width otherwise.
+pub fn paragraph_width(
+    p: &Preparation,
+    lines: &[Line],
+    region: Size,
+    expand: bool,
+) -> Abs {
+    if !region.x.is_finite() || (!expand && lines.iter().all(|line| line.fr().is_zero()))
+    {
+        region
+            .x
+            .min(p.hang + lines.iter().map(|line| line.width).max().unwrap_or_default())
+    } else {
+        region.x
+    }
+}
@@ -1,18 +1,27 @@
 use std::fmt::{self, Debug, Formatter};
 use std::ops::{Deref, DerefMut};
 
+use unicode_segmentation::GraphemeCursor;
+
 use super::*;
 use crate::engine::Engine;
-use crate::layout::{Abs, Dir, Em, Fr, Frame, FrameItem, Point};
-use crate::text::{Lang, TextElem};
+use crate::foundations::Smart;
+use crate::layout::{
+    Abs, Dir, Em, FixedAlignment, Fr, Frame, FrameItem, LineOverflow, Point, VAlignment,
+};
+use crate::text::{FontMetrics, Lang, TextElem};
 use crate::utils::Numeric;
+use crate::visualize::Path;
 
-const SHY: char = '\u{ad}';
+pub const SHY: char = '\u{ad}';
 const HYPHEN: char = '-';
 const EN_DASH: char = '–';
 const EM_DASH: char = '—';
 const LINE_SEPARATOR: char = '\u{2028}'; // We use LS to distinguish justified breaks.
 
+/// The smallest horizontal scale of glyphs when justifying.
+const MIN_SCALE: f64 = 0.01;
+
 /// A layouted line, consisting of a sequence of layouted paragraph items that
 /// are mostly borrowed from the preparation phase. This type enables you to
 /// measure the size of a line in a range before committing to building the
@@ -23,6 +32,8 @@ const LINE_SEPARATOR: char = '\u{2028}'; // We use LS to distinguish justified b
 /// line, respectively. But even those can partially reuse previous results when
 /// the break index is safe-to-break per rustybuzz.
 pub struct Line<'a> {
+    /// The range the line spans in the paragraph's text.
+    pub range: Range,
     /// The items the line is made of.
     pub items: Items<'a>,
     /// The exact natural width of the line.
@@ -38,6 +49,7 @@ impl<'a> Line<'a> {
     /// Create an empty line.
     pub fn empty() -> Self {
         Self {
+            range: 0..0,
             items: Items::new(),
             width: Abs::zero(),
             justify: false,
@@ -67,6 +79,108 @@ impl<'a> Line<'a> {
         count
     }
 
+    /// The total weight by which the glyphs that can take up additional space
+    /// share it in underfull lines.
+    pub fn justification_weight(&self) -> f64 {
+        let mut weight = 0.0;
+        for shaped in self.items.iter().filter_map(Item::text) {
+            weight += shaped.justification_weight();
+        }
+
+        // CJK character at line end should not be adjusted.
+        if self
+            .items
+            .last()
+            .and_then(Item::text)
+            .map(|s| s.cjk_justifiable_at_last())
+            .unwrap_or(false)
+        {
+            weight -= 1.0;
+        }
+
+        weight
+    }
+
+    /// The natural width of the line's justifiable word spaces.
+    pub fn space_width(&self) -> Abs {
+        self.items
+            .iter()
+            .filter_map(Item::text)
+            .map(|s| s.space_width())
+            .sum()
+    }
+
+    /// The part of the line's justification weight that falls on its word
+    /// spaces.
+    pub fn space_weight(&self) -> f64 {
+        self.items
+            .iter()
+            .filter_map(Item::text)
+            .map(|s| s.space_weight())
+            .sum()
+    }
+
+    /// How many gaps between glyph clusters are in the text where we can
+    /// insert additional space when justifying through word spaces and
+    /// justifiable glyphs isn't enough.
+    pub fn cluster_gaps(&self) -> usize {
+        let count: usize =
+            self.items.iter().filter_map(Item::text).map(|s| s.clusters()).sum();
+
+        // There is no gap after the last cluster at the line end.
+        if self.items.last().and_then(Item::text).is_some() {
+            count.saturating_sub(1)
+        } else {
+            count
+        }
+    }
+
+    /// The width of one kashida at each point at which the line's Arabic text
+    /// can be elongated for justification.
+    pub fn kashida_width(&self) -> Abs {
+        self.items
+            .iter()
+            .filter_map(Item::text)
+            .map(|s| {
+                let points = s.kashida_points();
+                points.iter().map(|&(.., advance)| advance).sum::<Em>().at(s.size)
+            })
+            .sum()
+    }
+
+    /// The horizontal centers of the line's justifiable spaces when it is set
+    /// at the given width.
+    ///
+    /// The extra space of a justified line is assumed to be spread evenly
+    /// across its spaces, so this is only an estimate of the final positions.
+    pub fn gaps(&self, width: Abs) -> Vec<Abs> {
+        let mut gaps = vec![];
+        let mut x = Abs::zero();
+        for item in self.items.iter() {
+            let Item::Text(shaped) = item else {
+                x += item.natural_width();
+                continue;
+            };
+
+            for glyph in shaped.glyphs.iter() {
+                let advance = glyph.x_advance.at(shaped.size);
+                if glyph.is_justifiable_space() {
+                    gaps.push(x + advance / 2.0);
+                }
+                x += advance;
+            }
+        }
+
+        if self.justify && !gaps.is_empty() {
+            let extra = (width - self.width) / gaps.len() as f64;
+            for (i, gap) in gaps.iter_mut().enumerate() {
+                *gap += extra * (i as f64 + 0.5);
+            }
+        }
+
+        gaps
+    }
+
     /// How much the line can stretch.
     pub fn stretchability(&self) -> Abs {
         self.items
@@ -76,6 +190,22 @@ impl<'a> Line<'a> {
             .sum()
     }
 
+    /// The natural width of the line's text.
+    pub fn text_width(&self) -> Abs {
+        self.items.iter().filter_map(Item::text).map(|s| s.width).sum()
+    }
+
+    /// How much the line's stretchable spacing can grow.
+    pub fn spacing_stretchability(&self) -> Abs {
+        self.items
+            .iter()
+            .map(|item| match item {
+                Item::Stretchable(_, stretch, _) => *stretch,
+                _ => Abs::zero(),
+            })
+            .sum()
+    }
+
     /// How much the line can shrink.
     pub fn shrinkability(&self) -> Abs {
         self.items
@@ -85,11 +215,53 @@ impl<'a> Line<'a> {
             .sum()
     }
 
+    /// End the line with an ellipsis on the side where the paragraph's
+    /// direction ends.
+    ///
+    /// The items are in visual order, so this is the last item in
+    /// left-to-right and the first item in right-to-left paragraphs, no matter
+    /// the direction of the text run there. If that item isn't text, the
+    /// ellipsis is placed in a run of its own next to it.
+    pub fn push_ellipsis(&mut self, engine: &Engine, p: &Preparation) {
+        let ltr = p.dir.is_positive();
+        let visible = |item: &Item| !matches!(item, Item::Tag(_));
+        let edge = if ltr {
+            self.items.iter().rposition(visible)
+        } else {
+            self.items.iter().position(visible)
+        };
+        let Some(mut edge) = edge else { return };
+
+        if self.items[edge].text().is_none() {
+            let closest = if ltr {
+                self.items[..edge].iter().rev().find_map(|entry| entry.text())
+            } else {
+                self.items[edge + 1..].iter().find_map(|entry| entry.text())
+            };
+            let Some(empty) = closest.map(ShapedText::empty) else { return };
+            if ltr {
+                edge += 1;
+            }
+            self.items.insert(edge, Item::Text(empty).into());
+        }
+
+        let Some(shaped) = self.items[edge].text_mut() else { return };
+        let prev = shaped.width;
+        if ltr {
+            shaped.push_ellipsis(engine, p.fallback);
+        } else {
+            shaped.prepend_ellipsis(engine, p.fallback);
+        }
+        self.width += shaped.width - prev;
+    }
+
     /// Whether the line has items with negative width.
     pub fn has_negative_width_items(&self) -> bool {
         self.items.iter().any(|item| match item {
-            Item::Absolute(amount, _) => *amount < Abs::zero(),
-            Item::Frame(frame, _) => frame.width() < Abs::zero(),
+            Item::Absolute(amount, _) | Item::Stretchable(amount, ..) => {
+                *amount < Abs::zero()
+            }
+            Item::Frame(frame, ..) => frame.width() < Abs::zero(),
             _ => false,
         })
     }
@@ -104,6 +276,83 @@ impl<'a> Line<'a> {
             })
             .sum()
     }
+
+    /// Determine the line's extents above and below its baseline without
+    /// building its frame.
+    ///
+    /// This matches the frame built by [`commit`], except for boxes with
+    /// fractional widths, which are only laid out when committing the line.
+    pub fn measure(&self, engine: &Engine) -> (Abs, Abs) {
+        let mut top = Abs::zero();
+        let mut bottom = Abs::zero();
+        let mut aligned = vec![];
+        for item in self.items.iter() {
+            match item {
+                Item::Text(shaped) => {
+                    let (above, below) = shaped.measure(engine);
+                    top.set_max(above);
+                    bottom.set_max(below);
+                }
+                Item::Frame(frame, styles, valign, overflow) if overflow.is_grow() => {
+                    if valign.is_auto() {
+                        let baseline = frame.baseline() + TextElem::baseline_in(*styles);
+                        top.set_max(baseline);
+                        bottom.set_max(frame.height() - baseline);
+                    } else {
+                        aligned.push((frame.height(), *valign));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (height, valign) in aligned {
+            grow(&mut top, &mut bottom, height, valign);
+        }
+
+        (top, bottom)
+    }
+
+    /// The largest text size in the line, if it contains any text.
+    pub fn text_size(&self) -> Option<Abs> {
+        self.items
+            .iter()
+            .filter_map(Item::text)
+            .map(|shaped| shaped.size)
+            .reduce(Abs::max)
+    }
+
+    /// The largest typographic ascender of the fonts in the line's text.
+    pub fn ascent(&self) -> Abs {
+        self.font_metric(|metrics| metrics.ascender)
+    }
+
+    /// The largest typographic descender of the fonts in the line's text.
+    pub fn descent(&self) -> Abs {
+        self.font_metric(|metrics| -metrics.descender)
+    }
+
+    /// The largest x-height of the fonts in the line's text.
+    pub fn x_height(&self) -> Abs {
+        self.font_metric(|metrics| metrics.x_height)
+    }
+
+    /// The largest cap-height of the fonts in the line's text.
+    pub fn cap_height(&self) -> Abs {
+        self.font_metric(|metrics| metrics.cap_height)
+    }
+
+    /// The maximum of a font metric over all glyphs in the line's text, scaled
+    /// to the size of the respective text run.
+    fn font_metric(&self, f: impl Fn(&FontMetrics) -> Em) -> Abs {
+        self.items
+            .iter()
+            .filter_map(Item::text)
+            .flat_map(|shaped| {
+                shaped.glyphs.iter().map(|g| f(g.font.metrics()).at(shaped.size))
+            })
+            .fold(Abs::zero(), Abs::max)
+    }
 }
 
 /// A dash at the end of a line.
@@ -125,12 +374,24 @@ pub fn line<'a>(
     breakpoint: Breakpoint,
     pred: Option<&Line>,
 ) -> Line<'a> {
+    // Make sure that reshaping never splits a grapheme cluster. Hyphenation
+    // and breaks within links may produce offsets inside of one. Both ends
+    // move forward, such that consecutive lines still meet.
+    let range =
+        snap_to_grapheme(p.text, range.start)..snap_to_grapheme(p.text, range.end);
+
     // The line's full text.
     let full = &p.text[range.clone()];
 
-    // Whether the line is justified.
+    // Whether the line is justified. The last line is only justified if
+    // explicitly requested.
     let justify = full.ends_with(LINE_SEPARATOR)
-        || (p.justify && breakpoint != Breakpoint::Mandatory);
+        || (p.justify
+            && if range.end == p.text.len() {
+                p.justify_last
+            } else {
+                breakpoint != Breakpoint::Mandatory
+            });
 
     // Process dashes.
     let dash = if breakpoint == Breakpoint::Hyphen || full.ends_with(SHY) {
@@ -143,11 +404,21 @@ pub fn line<'a>(
         None
     };
 
-    // Trim the line at the end, if necessary for this breakpoint.
-    let trim = range.start + breakpoint.trim(full).len();
+    // Trim the line at the end, if necessary for this breakpoint. Trailing
+    // whitespace may also be kept, in which case it takes up room, but doesn't
+    // take part in justification.
+    let trimmed = range.start + breakpoint.trim(full).len();
+    let trim = if p.keep_trailing_spaces && breakpoint == Breakpoint::Normal {
+        range.start + trim_ignorables(full).len()
+    } else {
+        trimmed
+    };
 
     // Collect the items for the line.
-    let mut items = collect_items(engine, p, range, trim);
+    let mut items = collect_items(engine, p, range.clone(), trim);
+    if trimmed < trim {
+        exclude_from_justification(&mut items, trimmed..trim);
+    }
 
     // Add a hyphen at the line start, if a previous dash should be repeated.
     if pred.map_or(false, |pred| should_repeat_hyphen(pred, full)) {
@@ -169,7 +440,75 @@ pub fn line<'a>(
     // Compute the line's width.
     let width = items.iter().map(Item::natural_width).sum();
 
-    Line { items, width, justify, dash }
+    Line { range, items, width, justify, dash }
+}
+
+/// Reshapes the text of a line with other styles, keeping its range.
+///
+/// The style chains are given in the order of the line's text items.
+pub fn restyle<'a>(
+    engine: &Engine,
+    p: &'a Preparation,
+    line: &'a Line<'a>,
+    styles: &'a [StyleChain<'a>],
+) -> Line<'a> {
+    let mut styles = styles.iter();
+    let mut items = Items::new();
+    for item in line.items.iter() {
+        match item {
+            Item::Text(shaped) => {
+                let styles = styles.next().copied().unwrap_or(shaped.styles);
+                items.push(Item::Text(shaped.restyle(engine, p.text, styles)));
+            }
+            _ => items.push(item),
+        }
+    }
+
+    // Restore the hyphen and the adjustments at the line's boundaries.
+    if line.dash == Some(Dash::Soft) {
+        if let Some(shaped) = items.last_text_mut() {
+            shaped.push_hyphen(engine, p.fallback);
+        }
+    }
+    adjust_cj_at_line_boundaries(p, &p.text[line.range.clone()], &mut items);
+
+    let width = items.iter().map(Item::natural_width).sum();
+    Line {
+        range: line.range.clone(),
+        items,
+        width,
+        justify: line.justify,
+        dash: line.dash,
+    }
+}
+
+/// Makes the glyphs in the given text range neither justifiable nor
+/// adjustable.
+fn exclude_from_justification(items: &mut Items, range: Range) {
+    for entry in items.iter_mut() {
+        let Some(text) = entry.text() else { continue };
+        if !text.glyphs.iter().any(|g| range.contains(&g.range.start)) {
+            continue;
+        }
+
+        let Some(shaped) = entry.text_mut() else { continue };
+        for glyph in shaped.glyphs.to_mut() {
+            if range.contains(&glyph.range.start) {
+                glyph.is_justifiable = false;
+                glyph.adjustability = Default::default();
+            }
+        }
+    }
+}
+
+/// Moves a text offset forward to the next grapheme cluster boundary, unless it
+/// already is one.
+fn snap_to_grapheme(text: &str, offset: usize) -> usize {
+    let mut cursor = GraphemeCursor::new(offset, text.len(), true);
+    match cursor.is_boundary(text, 0) {
+        Ok(false) => cursor.next_boundary(text, 0).ok().flatten().unwrap_or(offset),
+        _ => offset,
+    }
 }
 
 /// Collects / reshapes all items for the line with the given `range`.
@@ -199,22 +538,20 @@ fn collect_items<'a>(
     });
 
     // Trim weak spacing at the start of the line.
-    let prefix = items
-        .iter()
-        .take_while(|item| matches!(item, Item::Absolute(_, true)))
-        .count();
+    let prefix = items.iter().take_while(|item| item.is_weak()).count();
     if prefix > 0 {
         items.drain(..prefix);
     }
 
     // Trim weak spacing at the end of the line.
-    while matches!(items.last(), Some(Item::Absolute(_, true))) {
+    while items.last().is_some_and(Item::is_weak) {
         items.pop();
     }
 
     // Add fallback text to expand the line height, if necessary.
     if !items.iter().any(|item| matches!(item, Item::Text(_))) {
-        if let Some(fallback) = fallback {
+        let strut = || p.strut.clone().map(|shaped| Item::Text(shaped).into());
+        if let Some(fallback) = fallback.or_else(strut) {
             items.push(fallback);
         }
     }
@@ -288,7 +625,7 @@ fn collect_range<'a>(
             *fallback = Some(ItemEntry::from(Item::Text(shaped.empty())));
         } else if split {
             // When the item is split in half, reshape it.
-            let reshaped = shaped.reshape(engine, sliced);
+            let reshaped = shaped.reshape(engine, p.text, sliced);
             items.push(Item::Text(reshaped));
         } else {
             // When the item is fully contained, just keep it.
@@ -414,6 +751,176 @@ pub fn commit(
     full: Abs,
     shrink: bool,
 ) -> SourceResult<Frame> {
+    let fr = line.fr();
+    let Adjustment {
+        mut offset,
+        mut remaining,
+        justification_ratio,
+        extra_justification,
+        extra_tracking,
+        kashidas,
+        spacing_ratio,
+        scale,
+    } = adjust(p, line, width, shrink);
+
+    let mut top = Abs::zero();
+    let mut bottom = Abs::zero();
+
+    // Build the frames and determine the height and baseline. Frames that are
+    // aligned relative to the line instead of its baseline or that may
+    // overflow the line don't contribute here.
+    let mut frames = vec![];
+    for (i, item) in line.items.iter().enumerate() {
+        let mut push = |offset: &mut Abs,
+                        frame: Frame,
+                        valign: Smart<VAlignment>,
+                        overflow: LineOverflow| {
+            let width = frame.width();
+            if valign.is_auto() && overflow.is_grow() {
+                top.set_max(frame.baseline());
+                bottom.set_max(frame.size().y - frame.baseline());
+            }
+            frames.push((*offset, frame, valign, overflow));
+            *offset += width;
+        };
+
+        match item {
+            Item::Absolute(v, _) => {
+                offset += *v;
+            }
+            Item::Stretchable(v, stretch, _) => {
+                offset += *v + *stretch * spacing_ratio;
+            }
+            Item::Fractional(v, elem) => {
+                let amount = v.share(fr, remaining);
+                if let Some((elem, loc, styles)) = elem {
+                    let region = Size::new(amount, full);
+                    let mut frame =
+                        elem.layout(engine, loc.relayout(), *styles, region)?;
+                    frame.post_process(*styles);
+                    frame.translate(Point::with_y(TextElem::baseline_in(*styles)));
+                    push(
+                        &mut offset,
+                        frame,
+                        elem.valign(*styles),
+                        elem.line_overflow(*styles),
+                    );
+                } else {
+                    offset += amount;
+                }
+            }
+            Item::Text(shaped) => {
+                let mut frame = shaped.build(
+                    engine,
+                    &p.spans,
+                    justification_ratio,
+                    extra_justification,
+                    extra_tracking,
+                    i + 1 == line.items.len(),
+                    kashidas,
+                    scale,
+                );
+                frame.post_process(shaped.styles);
+                push(&mut offset, frame, Smart::Auto, LineOverflow::Grow);
+            }
+            Item::Frame(frame, styles, valign, overflow) => {
+                let mut frame = frame.clone();
+                frame.post_process(*styles);
+                frame.translate(Point::with_y(TextElem::baseline_in(*styles)));
+                push(&mut offset, frame, *valign, *overflow);
+            }
+            Item::Tag(tag) => {
+                let mut frame = Frame::soft(Size::zero());
+                frame.push(Point::zero(), FrameItem::Tag((*tag).clone()));
+                frames.push((offset, frame, Smart::Auto, LineOverflow::Grow));
+            }
+            Item::Skip(_) => {}
+        }
+    }
+
+    // Remaining space is distributed now.
+    if !fr.is_zero() {
+        remaining = Abs::zero();
+    }
+
+    // Grow the line such that frames aligned relative to it fit.
+    for (_, frame, valign, overflow) in &frames {
+        if overflow.is_grow() {
+            grow(&mut top, &mut bottom, frame.height(), *valign);
+        }
+    }
+
+    let size = Size::new(width, top + bottom);
+    let mut output = Frame::soft(size);
+    output.set_baseline(top);
+
+    // Construct the line's frame.
+    for (offset, frame, valign, overflow) in frames {
+        let x = offset + align(p, line).position(remaining);
+        let y = match valign {
+            Smart::Auto => top - frame.baseline(),
+            Smart::Custom(VAlignment::Top) => Abs::zero(),
+            Smart::Custom(VAlignment::Horizon) => (size.y - frame.height()) / 2.0,
+            Smart::Custom(VAlignment::Bottom) => size.y - frame.height(),
+        };
+
+        if overflow == LineOverflow::Clip {
+            // Cut the frame off at the line's top and bottom edges.
+            let mut clipped = Frame::soft(Size::new(frame.width(), size.y));
+            clipped.push_frame(Point::with_y(y), frame);
+            clipped.clip(Path::rect(clipped.size()));
+            output.push_frame(Point::with_x(x), clipped);
+        } else {
+            output.push_frame(Point::new(x, y), frame);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Grow a line's extents above and below the baseline such that a frame of
+/// the given height that is aligned relative to the line fits.
+fn grow(top: &mut Abs, bottom: &mut Abs, height: Abs, valign: Smart<VAlignment>) {
+    let missing = height - (*top + *bottom);
+    if missing <= Abs::zero() {
+        return;
+    }
+
+    match valign {
+        Smart::Auto => {}
+        Smart::Custom(VAlignment::Top) => *bottom += missing,
+        Smart::Custom(VAlignment::Horizon) => {
+            *top += missing / 2.0;
+            *bottom += missing / 2.0;
+        }
+        Smart::Custom(VAlignment::Bottom) => *top += missing,
+    }
+}
+
+/// How the items of a line are spaced out to fill its width.
+struct Adjustment {
+    /// The offset of the line's first item.
+    offset: Abs,
+    /// The space that remains after justification, to be distributed
+    /// according to the alignment or by fractional spacing.
+    remaining: Abs,
+    /// How much of their adjustability the glyphs use, from -1 to 1.
+    justification_ratio: f64,
+    /// Extra space after each justifiable glyph, per unit of its weight.
+    extra_justification: Abs,
+    /// Extra space after each glyph cluster.
+    extra_tracking: Abs,
+    /// The number of kashidas to insert at each kashida point.
+    kashidas: usize,
+    /// How much of their stretchability the stretchable spacings use, from 0
+    /// to 1.
+    spacing_ratio: f64,
+    /// The horizontal scale of the glyphs.
+    scale: f64,
+}
+
+/// Determine how to space out a line's items to fill the given width.
+fn adjust(p: &Preparation, line: &Line, width: Abs, shrink: bool) -> Adjustment {
     let mut remaining = width - line.width - p.hang;
     let mut offset = Abs::zero();
 
@@ -459,6 +966,22 @@ pub fn commit(
     let fr = line.fr();
     let mut justification_ratio = 0.0;
     let mut extra_justification = Abs::zero();
+    let mut extra_tracking = Abs::zero();
+    let mut kashidas = 0;
+    let mut spacing_ratio = 0.0;
+    let mut scale = 1.0;
+
+    // Scale the glyphs within the permitted range to take up or give back
+    // some of the remaining space.
+    let text_width = line.text_width();
+    let mut rescale = |remaining: &mut Abs| {
+        let max = p.justify_scale.get();
+        if max > 0.0 && text_width > Abs::zero() {
+            let amount = (*remaining / text_width).clamp(-max, max);
+            scale = (1.0 + amount).max(MIN_SCALE);
+            *remaining -= text_width * (scale - 1.0);
+        }
+    };
 
     let shrinkability = line.shrinkability();
     let stretchability = line.stretchability();
@@ -466,94 +989,159 @@ pub fn commit(
         // Attempt to reduce the length of the line, using shrinkability.
         justification_ratio = (remaining / shrinkability).max(-1.0);
         remaining = (remaining + shrinkability).min(Abs::zero());
+        if remaining < Abs::zero() {
+            rescale(&mut remaining);
+        }
     } else if line.justify && fr.is_zero() {
+        // Let stretchable spacing absorb the remaining space first, up to how
+        // much it may grow.
+        let spacing_stretchability = line.spacing_stretchability();
+        if spacing_stretchability > Abs::zero() && remaining > Abs::zero() {
+            spacing_ratio = (remaining / spacing_stretchability).min(1.0);
+            remaining -= spacing_stretchability * spacing_ratio;
+        }
+
+        // Prefer elongating Arabic words with kashidas over widening the
+        // spaces between them, as far as whole kashidas fit.
+        let kashida_width = line.kashida_width();
+        if p.justify_kashida && kashida_width > Abs::zero() && remaining > Abs::zero() {
+            kashidas = (remaining / kashida_width).floor() as usize;
+            remaining -= kashida_width * kashidas as f64;
+        }
+
+        // Determine how much the line's word spaces may grow at most. Other
+        // justifiable glyphs, like in Chinese or Japanese text, are not
+        // limited.
+        let spaces = line.space_width();
+        let limit = p
+            .justify_stretch
+            .filter(|_| spaces > Abs::zero())
+            .map(|ratio| spaces * ratio.get());
+
         // Attempt to increase the length of the line, using stretchability.
         if stretchability > Abs::zero() {
-            justification_ratio = (remaining / stretchability).min(1.0);
-            remaining = (remaining - stretchability).max(Abs::zero());
+            let max = limit.map_or(1.0, |limit| (limit / stretchability).min(1.0));
+            justification_ratio = (remaining / stretchability).min(max);
+            remaining = (remaining - stretchability * max).max(Abs::zero());
+        }
+
+        let weight = line.justification_weight();
+        if weight > 0.0 && remaining > Abs::zero() {
+            // Underfull line, distribute the extra space according to the
+            // glyphs' weights. Once the word spaces' share reaches the limit,
+            // anything beyond is left as slack and placed according to the
+            // alignment.
+            let share = line.space_weight() / weight;
+            let extra = match limit {
+                Some(limit) if share > 0.0 => {
+                    let room = (limit - stretchability).max(Abs::zero());
+                    remaining.min(room / share)
+                }
+                _ => remaining,
+            };
+            extra_justification = extra / weight;
+            remaining -= extra;
+        }
+
+        if remaining > Abs::zero() {
+            rescale(&mut remaining);
         }
 
-        let justifiables = line.justifiables();
-        if justifiables > 0 && remaining > Abs::zero() {
-            // Underfull line, distribute the extra space.
-            extra_justification = remaining / justifiables as f64;
+        // Spread whatever is still left between the glyph clusters.
+        let gaps = line.cluster_gaps();
+        if p.justify_glyphs && gaps > 0 && remaining > Abs::zero() {
+            extra_tracking = remaining / gaps as f64;
             remaining = Abs::zero();
         }
     }
 
-    let mut top = Abs::zero();
-    let mut bottom = Abs::zero();
+    Adjustment {
+        offset,
+        remaining,
+        justification_ratio,
+        extra_justification,
+        extra_tracking,
+        kashidas,
+        spacing_ratio,
+        scale,
+    }
+}
 
-    // Build the frames and determine the height and baseline.
-    let mut frames = vec![];
-    for item in line.items.iter() {
-        let mut push = |offset: &mut Abs, frame: Frame| {
-            let width = frame.width();
-            top.set_max(frame.baseline());
-            bottom.set_max(frame.size().y - frame.baseline());
-            frames.push((*offset, frame));
-            *offset += width;
-        };
+/// How to align a line within the paragraph's width.
+fn align(p: &Preparation, line: &Line) -> FixedAlignment {
+    if line.range.end == p.text.len() {
+        p.last_line_align.unwrap_or(p.align)
+    } else {
+        p.align
+    }
+}
 
+/// Determine the extents of the glyphs that belong to the given range of the
+/// paragraph's text in the line's frame as built by [`commit`] with the same
+/// arguments.
+///
+/// The glyphs extend vertically from the top to the bottom edge of their
+/// text, relative to the given baseline of the line. The extents are in
+/// visual order. Since the line's items are already reordered, a range
+/// spanning text of multiple directions may map to multiple disjoint groups of
+/// glyphs.
+pub fn glyph_extents(
+    engine: &Engine,
+    p: &Preparation,
+    line: &Line,
+    width: Abs,
+    shrink: bool,
+    baseline: Abs,
+    range: Range,
+) -> Vec<GlyphExtent> {
+    let fr = line.fr();
+    let Adjustment {
+        mut offset,
+        remaining,
+        justification_ratio,
+        extra_justification,
+        extra_tracking,
+        kashidas,
+        spacing_ratio,
+        scale,
+    } = adjust(p, line, width, shrink);
+
+    // Remaining space is distributed by fractional spacing if there is any.
+    let shift =
+        if fr.is_zero() { align(p, line).position(remaining) } else { Abs::zero() };
+
+    let mut extents = vec![];
+    for (i, item) in line.items.iter().enumerate() {
         match item {
-            Item::Absolute(v, _) => {
-                offset += *v;
-            }
-            Item::Fractional(v, elem) => {
-                let amount = v.share(fr, remaining);
-                if let Some((elem, loc, styles)) = elem {
-                    let region = Size::new(amount, full);
-                    let mut frame =
-                        elem.layout(engine, loc.relayout(), *styles, region)?;
-                    frame.post_process(*styles);
-                    frame.translate(Point::with_y(TextElem::baseline_in(*styles)));
-                    push(&mut offset, frame);
-                } else {
-                    offset += amount;
-                }
-            }
+            Item::Absolute(v, _) => offset += *v,
+            Item::Stretchable(v, stretch, _) => offset += *v + *stretch * spacing_ratio,
+            Item::Fractional(v, _) => offset += v.share(fr, remaining),
             Item::Text(shaped) => {
-                let mut frame = shaped.build(
-                    engine,
-                    &p.spans,
+                let (glyphs, width) = shaped.glyph_extents(
+                    range.clone(),
                     justification_ratio,
                     extra_justification,
+                    extra_tracking,
+                    i + 1 == line.items.len(),
+                    kashidas,
+                    scale,
                 );
-                frame.post_process(shaped.styles);
-                push(&mut offset, frame);
+                let (above, below) = shaped.measure(engine);
+                let y = baseline + TextElem::baseline_in(shaped.styles);
+                extents.extend(glyphs.into_iter().map(|(x, w)| GlyphExtent {
+                    x: shift + offset + x,
+                    width: w,
+                    top: y - above,
+                    bottom: y + below,
+                }));
+                offset += width;
             }
-            Item::Frame(frame, styles) => {
-                let mut frame = frame.clone();
-                frame.post_process(*styles);
-                frame.translate(Point::with_y(TextElem::baseline_in(*styles)));
-                push(&mut offset, frame);
-            }
-            Item::Tag(tag) => {
-                let mut frame = Frame::soft(Size::zero());
-                frame.push(Point::zero(), FrameItem::Tag((*tag).clone()));
-                frames.push((offset, frame));
-            }
-            Item::Skip(_) => {}
+            Item::Frame(frame, ..) => offset += frame.width(),
+            Item::Tag(_) | Item::Skip(_) => {}
         }
     }
 
-    // Remaining space is distributed now.
-    if !fr.is_zero() {
-        remaining = Abs::zero();
-    }
-
-    let size = Size::new(width, top + bottom);
-    let mut output = Frame::soft(size);
-    output.set_baseline(top);
-
-    // Construct the line's frame.
-    for (offset, frame) in frames {
-        let x = offset + p.align.position(remaining);
-        let y = top - frame.baseline();
-        output.push_frame(Point::new(x, y), frame);
-    }
-
-    Ok(output)
+    extents
 }
 
 /// How much a character should hang into the end margin.
@@ -1,3 +1,4 @@
+use std::num::NonZeroUsize;
 use std::ops::{Add, Sub};
 
 use icu_properties::maps::{CodePointMapData, CodePointMapDataBorrowed};
@@ -13,7 +14,7 @@ use unicode_segmentation::UnicodeSegmentation;
 use super::*;
 use crate::engine::Engine;
 use crate::layout::{Abs, Em};
-use crate::model::Linebreaks;
+use crate::model::{Linebreaks, Overflow};
 use crate::syntax::link_prefix;
 use crate::text::{Lang, TextElem};
 
@@ -23,12 +24,21 @@ type Cost = f64;
 // Cost parameters.
 const DEFAULT_HYPH_COST: Cost = 0.5;
 const DEFAULT_RUNT_COST: Cost = 0.5;
+const DEFAULT_RIVER_COST: Cost = 0.1;
 const CONSECUTIVE_DASH_COST: Cost = 0.3;
+const LOOSE_COST: Cost = 1_000.0;
+const HYPHEN_LIMIT_COST: Cost = 1_000.0;
 const MAX_COST: Cost = 1_000_000.0;
 const MIN_RATIO: f64 = -1.0;
 const MIN_APPROX_RATIO: f64 = -0.5;
 const BOUND_EPS: f64 = 1e-3;
 
+/// Characters before which lines are never broken, even after a space.
+const NOBREAK_BEFORE: &[char] = &[')', ']', '}', '%', '‰', '‱'];
+
+/// Characters after which lines are never broken, even before a space.
+const NOBREAK_AFTER: &[char] = &['(', '[', '{', '¿', '¡', '„'];
+
 /// The ICU blob data.
 fn blob() -> BlobDataProvider {
     BlobDataProvider::try_new_from_static_blob(typst_assets::icu::ICU).unwrap()
@@ -72,10 +82,7 @@ pub enum Breakpoint {
 impl Breakpoint {
     /// Trim a line before this breakpoint.
     pub fn trim(self, line: &str) -> &str {
-        // Trim default ignorables.
-        let ignorable = DEFAULT_IGNORABLE_DATA.as_borrowed();
-        let line = line.trim_end_matches(|c| ignorable.contains(c));
-
+        let line = trim_ignorables(line);
         match self {
             // Trim whitespace.
             Self::Normal => line.trim_end_matches(char::is_whitespace),
@@ -100,6 +107,12 @@ impl Breakpoint {
     }
 }
 
+/// Trim default ignorables at the end of a line.
+pub fn trim_ignorables(line: &str) -> &str {
+    let ignorable = DEFAULT_IGNORABLE_DATA.as_borrowed();
+    line.trim_end_matches(|c| ignorable.contains(c))
+}
+
 /// Breaks the paragraph into lines.
 pub fn linebreak<'a>(
     engine: &Engine,
@@ -114,12 +127,141 @@ pub fn linebreak<'a>(
         }
     });
 
-    match linebreaks {
+    let mut lines = match linebreaks {
         Linebreaks::Simple => linebreak_simple(engine, p, width),
         Linebreaks::Optimized => linebreak_optimized(engine, p, width),
+        Linebreaks::Balanced => linebreak_balanced(engine, p, width),
+    };
+
+    if let Some(max) = p.max_lines {
+        clamp(engine, p, width, &mut lines, max);
+    }
+
+    if p.overflow == Overflow::Truncate {
+        truncate(engine, p, width, &mut lines);
+    }
+
+    lines
+}
+
+/// Determines the paragraph's intrinsic widths without breaking it into lines.
+///
+/// Returns the width of the widest piece between two break opportunities
+/// (min-content) and the width of the widest line when breaking only where
+/// it is mandatory (max-content).
+pub fn intrinsic_widths(engine: &Engine, p: &Preparation) -> (Abs, Abs) {
+    let mut min = Abs::zero();
+    let mut max = Abs::zero();
+    let mut start = 0;
+    let mut line_start = 0;
+
+    breakpoints(p, |end, breakpoint| {
+        min.set_max(line(engine, p, start..end, breakpoint, None).width);
+        start = end;
+
+        if breakpoint == Breakpoint::Mandatory {
+            max.set_max(line(engine, p, line_start..end, breakpoint, None).width);
+            line_start = end;
+        }
+    });
+
+    (min + p.hang, max + p.hang)
+}
+
+/// Limits the paragraph to at most `max` lines and shortens the last remaining
+/// line such that it fits with an ellipsis if any text was cut off.
+fn clamp<'a>(
+    engine: &Engine,
+    p: &'a Preparation<'a>,
+    width: Abs,
+    lines: &mut Vec<Line<'a>>,
+    max: NonZeroUsize,
+) {
+    if lines.len() <= max.get() {
+        return;
+    }
+
+    lines.truncate(max.get());
+    let Some(last) = lines.pop() else { return };
+    let mut line = ellipsize(engine, p, width, &last, lines.last());
+    keep_tags(p, &mut line, last.range.end..p.text.len());
+    lines.push(line);
+}
+
+/// Shortens all lines that are too wide such that they fit with an ellipsis.
+fn truncate<'a>(
+    engine: &Engine,
+    p: &'a Preparation<'a>,
+    width: Abs,
+    lines: &mut [Line<'a>],
+) {
+    for i in 0..lines.len() {
+        let shrink = if p.justify { lines[i].shrinkability() } else { Abs::zero() };
+        if width.fits(lines[i].width - shrink) {
+            continue;
+        }
+
+        let pred = i.checked_sub(1).map(|j| &lines[j]);
+        let line = ellipsize(engine, p, width, &lines[i], pred);
+        lines[i] = line;
     }
 }
 
+/// Adds the tags in a range of text that was cut off to the end of a line.
+///
+/// This way, elements in the cut text can still be located and the start and
+/// end tags of elements spanning the cut stay balanced.
+fn keep_tags<'a>(p: &'a Preparation<'a>, line: &mut Line<'a>, cut: Range) {
+    for (_, item) in p.slice(cut) {
+        if let Item::Tag(tag) = item {
+            line.items.push(Item::Tag(*tag));
+        }
+    }
+}
+
+/// Shortens a line such that it fits with an ellipsis.
+fn ellipsize<'a>(
+    engine: &Engine,
+    p: &'a Preparation<'a>,
+    width: Abs,
+    full: &Line<'a>,
+    pred: Option<&Line>,
+) -> Line<'a> {
+    let start = full.range.start;
+
+    // The grapheme boundaries at which the shortened line may end.
+    let bounds: Vec<usize> = p.text[full.range.clone()]
+        .grapheme_indices(true)
+        .skip(1)
+        .map(|(i, _)| start + i)
+        .chain([full.range.end])
+        .collect();
+
+    let ellipsized = |end| {
+        let mut line = line(engine, p, start..end, Breakpoint::Normal, pred);
+        line.push_ellipsis(engine, p);
+        line.justify = false;
+        line
+    };
+
+    // Find the longest line that fits with the ellipsis through binary search.
+    // If not even a single grapheme fits, we take it anyway.
+    let (mut lo, mut hi) = (0, bounds.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if width.fits(ellipsized(bounds[mid]).width) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let end = bounds[lo.saturating_sub(1)];
+    let mut line = ellipsized(end);
+    keep_tags(p, &mut line, end..full.range.end);
+    line
+}
+
 /// Performs line breaking in simple first-fit style. This means that we build
 /// lines greedily, always taking the longest possible line. This may lead to
 /// very unbalanced line, but is fast and simple.
@@ -133,6 +275,12 @@ fn linebreak_simple<'a>(
     let mut start = 0;
     let mut last = None;
 
+    // In a justified paragraph, a line also fits if it can be shrunk enough.
+    let fits = |attempt: &Line| {
+        let shrink = if p.justify { attempt.shrinkability() } else { Abs::zero() };
+        width.fits(attempt.width - shrink)
+    };
+
     breakpoints(p, |end, breakpoint| {
         // Compute the line and its size.
         let mut attempt = line(engine, p, start..end, breakpoint, lines.last());
@@ -140,7 +288,7 @@ fn linebreak_simple<'a>(
         // If the line doesn't fit anymore, we push the last fitting attempt
         // into the stack and rebuild the line from the attempt's end. The
         // resulting line cannot be broken up further.
-        if !width.fits(attempt.width) {
+        if !fits(&attempt) {
             if let Some((last_attempt, last_end)) = last.take() {
                 lines.push(last_attempt);
                 start = last_end;
@@ -148,14 +296,28 @@ fn linebreak_simple<'a>(
             }
         }
 
+        // If the line still doesn't fit, there is no softer break opportunity
+        // left and we may need to break the overlong text as a last resort.
+        if p.emergency_breaks && !fits(&attempt) {
+            attempt = emergency_break(
+                engine, p, width, &mut lines, start, end, breakpoint, attempt,
+            );
+        }
+
         // Finish the current line if there is a mandatory line break (i.e. due
         // to "\n") or if the line doesn't fit horizontally already since then
         // no shorter line will be possible.
-        if breakpoint == Breakpoint::Mandatory || !width.fits(attempt.width) {
+        if breakpoint == Breakpoint::Mandatory || !fits(&attempt) {
             lines.push(attempt);
             start = end;
             last = None;
-        } else {
+        } else if attempt.dash != Some(Dash::Soft)
+            || !(hyphen_limit_reached(p, &lines)
+                || filled_enough(p, width, last.as_ref().map(|(line, _)| line)))
+        {
+            // If too many lines in a row already ended in a hyphenation or the
+            // last non-hyphenated attempt fills the line well enough, we don't
+            // consider this attempt and keep the last non-hyphenated one.
             last = Some((attempt, end));
         }
     });
@@ -167,6 +329,39 @@ fn linebreak_simple<'a>(
     lines
 }
 
+/// Whether the lines preceding the next one already exhaust the limit of
+/// consecutive hyphenated lines.
+fn hyphen_limit_reached(p: &Preparation, lines: &[Line]) -> bool {
+    p.hyphenate_lines.is_some_and(|limit| {
+        lines
+            .iter()
+            .rev()
+            .take_while(|line| line.dash == Some(Dash::Soft))
+            .count()
+            >= limit
+    })
+}
+
+/// Compute the cost for a line that ends the given number of consecutive
+/// hyphenated lines.
+fn hyphen_limit_cost(p: &Preparation, hyphens: usize) -> Cost {
+    if p.hyphenate_lines.is_some_and(|limit| hyphens > limit) {
+        HYPHEN_LIMIT_COST
+    } else {
+        0.0
+    }
+}
+
+/// Whether the last fitting attempt of a ragged paragraph comes close enough
+/// to the width that a hyphenated attempt isn't needed.
+fn filled_enough(p: &Preparation, width: Abs, last: Option<&Line>) -> bool {
+    let Some(tolerance) = p.ragged_tolerance else { return false };
+    !p.justify
+        && last.is_some_and(|line| {
+            line.dash != Some(Dash::Soft) && width - line.width <= tolerance.of(width)
+        })
+}
+
 /// Performs line breaking in optimized Knuth-Plass style. Here, we use more
 /// context to determine the line breaks than in the simple first-fit style. For
 /// example, we might choose to cut a line short even though there is still a
@@ -194,8 +389,16 @@ fn linebreak_optimized<'a>(
 
     // Determines the exact costs of a likely good layout through Knuth-Plass
     // with approximate metrics. We can use this cost as an upper bound to prune
-    // the search space in our proper optimization pass below.
-    let upper_bound = linebreak_optimized_approximate(engine, p, width, &metrics);
+    // the search space in our proper optimization pass below. The limit of
+    // consecutive hyphenated lines makes a line's cost depend on the whole
+    // chain of lines before it, so the optimization pass might not find the
+    // approximate layout again. The same holds for the river cost, which
+    // depends on the previous line. We don't prune in these cases.
+    let upper_bound = if p.hyphenate_lines.is_some() || metrics.river_cost > 0.0 {
+        Cost::INFINITY
+    } else {
+        linebreak_optimized_approximate(engine, p, width, &metrics)
+    };
 
     // Using the upper bound, perform exact optimized linebreaking.
     linebreak_optimized_bounded(engine, p, width, &metrics, upper_bound)
@@ -217,10 +420,19 @@ fn linebreak_optimized_bounded<'a>(
         total: Cost,
         line: Line<'a>,
         end: usize,
+        breakpoint: Breakpoint,
+        hyphens: usize,
     }
 
     // Dynamic programming table.
-    let mut table = vec![Entry { pred: 0, total: 0.0, line: Line::empty(), end: 0 }];
+    let mut table = vec![Entry {
+        pred: 0,
+        total: 0.0,
+        line: Line::empty(),
+        end: 0,
+        breakpoint: Breakpoint::Mandatory,
+        hyphens: 0,
+    }];
 
     let mut active = 0;
     let mut prev_end = 0;
@@ -273,8 +485,18 @@ fn linebreak_optimized_bounded<'a>(
                 active += 1;
             }
 
-            // The total cost of this line and its chain of predecessors.
-            let total = pred.total + line_cost;
+            // The number of consecutive hyphenated lines up to this one.
+            let hyphens =
+                if attempt.dash == Some(Dash::Soft) { pred.hyphens + 1 } else { 0 };
+
+            // The total cost of this line and its chain of predecessors. The
+            // river and hyphen limit costs are kept out of `line_cost` because
+            // they depend on the predecessor, so they must not enter the lower
+            // bound below.
+            let total = pred.total
+                + line_cost
+                + river_cost(metrics, width, &pred.line, &attempt)
+                + hyphen_limit_cost(p, hyphens);
 
             // If the line is already underfull (`line_ratio > 0`), any shorter
             // slice of the line will be even more underfull. So it'll only get
@@ -297,7 +519,14 @@ fn linebreak_optimized_bounded<'a>(
 
             // If this attempt is better than what we had before, take it!
             if best.as_ref().map_or(true, |best| best.total >= total) {
-                best = Some(Entry { pred: pred_index, total, line: attempt, end });
+                best = Some(Entry {
+                    pred: pred_index,
+                    total,
+                    line: attempt,
+                    end,
+                    breakpoint,
+                    hyphens,
+                });
             }
         }
 
@@ -312,7 +541,7 @@ fn linebreak_optimized_bounded<'a>(
     });
 
     // Retrace the best path.
-    let mut lines = Vec::with_capacity(16);
+    let mut path = Vec::with_capacity(16);
     let mut idx = table.len() - 1;
 
     // This should only happen if our bound was faulty. Which shouldn't happen!
@@ -327,14 +556,183 @@ fn linebreak_optimized_bounded<'a>(
     while idx != 0 {
         table.truncate(idx + 1);
         let entry = table.pop().unwrap();
-        lines.push(entry.line);
         idx = entry.pred;
+        path.push(entry);
+    }
+
+    let mut lines = Vec::with_capacity(path.len());
+    let mut start = 0;
+    for entry in path.into_iter().rev() {
+        let mut line = entry.line;
+
+        // Lines that don't fit even with maximum shrinking contain overlong
+        // text, which we may need to break as a last resort.
+        let shrink = if line.justify { line.shrinkability() } else { Abs::zero() };
+        if p.emergency_breaks && !width.fits(line.width - shrink) {
+            line = emergency_break(
+                engine,
+                p,
+                width,
+                &mut lines,
+                start,
+                entry.end,
+                entry.breakpoint,
+                line,
+            );
+        }
+
+        lines.push(line);
+        start = entry.end;
+    }
+
+    lines
+}
+
+/// Performs line breaking such that all lines are about equally long.
+///
+/// To this end, we first determine how many lines a first-fit layout needs.
+/// Then, we minimize the deviation of each line from the average width of
+/// those lines with a Knuth-Plass style search.
+#[typst_macros::time]
+fn linebreak_balanced<'a>(
+    engine: &Engine,
+    p: &'a Preparation<'a>,
+    width: Abs,
+) -> Vec<Line<'a>> {
+    /// An entry in the dynamic programming table for balancing.
+    struct Entry<'a> {
+        pred: usize,
+        total: Cost,
+        line: Line<'a>,
+        end: usize,
+    }
+
+    let greedy = linebreak_simple(engine, p, width);
+    if greedy.len() < 2 {
+        return greedy;
+    }
+
+    let metrics = CostMetrics::compute(p);
+    let target = greedy.iter().map(|line| line.width).sum::<Abs>() / greedy.len() as f64;
+
+    // Dynamic programming table.
+    let mut table = vec![Entry { pred: 0, total: 0.0, line: Line::empty(), end: 0 }];
+    let mut active = 0;
+
+    breakpoints(p, |end, breakpoint| {
+        // Find the optimal predecessor.
+        let mut best: Option<Entry> = None;
+
+        for (pred_index, pred) in table.iter().enumerate().skip(active) {
+            let attempt = line(engine, p, pred.end..end, breakpoint, Some(&pred.line));
+
+            // Lines starting even earlier would only be longer, so if this
+            // one doesn't fit, its start becomes inactive.
+            let overfull = !width.fits(attempt.width);
+            if overfull && active == pred_index {
+                active += 1;
+            }
+
+            // The cost grows quadratically with the deviation from the target.
+            let mut cost = if overfull {
+                MAX_COST
+            } else {
+                ((attempt.width - target) / width).powi(2)
+            };
+
+            // Penalize hyphenation.
+            if breakpoint == Breakpoint::Hyphen {
+                cost += metrics.hyph_cost;
+            }
+
+            // If this attempt is better than what we had before, take it!
+            let total = pred.total + cost;
+            if best.as_ref().map_or(true, |best| best.total >= total) {
+                best = Some(Entry { pred: pred_index, total, line: attempt, end });
+            }
+        }
+
+        // If this is a mandatory break, all breakpoints before this one become
+        // inactive since no line can span over the mandatory break.
+        if breakpoint == Breakpoint::Mandatory {
+            active = table.len();
+        }
+
+        table.extend(best);
+    });
+
+    // Retrace the best path.
+    let mut lines = Vec::with_capacity(greedy.len());
+    let mut idx = table.len() - 1;
+    while idx != 0 {
+        table.truncate(idx + 1);
+        let entry = table.pop().unwrap();
+        idx = entry.pred;
+        lines.push(entry.line);
     }
 
     lines.reverse();
+
+    // Balancing shouldn't take more lines than necessary and doesn't help
+    // with text that doesn't fit at all.
+    if lines.len() > greedy.len() || lines.iter().any(|line| !width.fits(line.width)) {
+        return greedy;
+    }
+
     lines
 }
 
+/// Breaks overlong text at grapheme boundaries as a last resort.
+///
+/// This is used when even the shortest possible line for `start..end` doesn't
+/// fit. Pushes lines filled up to the available width into `lines` until the
+/// remainder fits and then returns the remaining line ending at `end`.
+#[allow(clippy::too_many_arguments)]
+fn emergency_break<'a>(
+    engine: &Engine,
+    p: &'a Preparation<'a>,
+    width: Abs,
+    lines: &mut Vec<Line<'a>>,
+    mut start: usize,
+    end: usize,
+    breakpoint: Breakpoint,
+    mut attempt: Line<'a>,
+) -> Line<'a> {
+    while !width.fits(attempt.width) {
+        // The grapheme boundaries within the remaining text, excluding its
+        // start.
+        let bounds: Vec<usize> = p.text[start..end]
+            .grapheme_indices(true)
+            .skip(1)
+            .map(|(i, _)| start + i)
+            .collect();
+
+        // Find the longest prefix that still fits through binary search. If
+        // not even a single grapheme fits, we take it anyway to make progress.
+        let (mut lo, mut hi) = (0, bounds.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let prefix =
+                line(engine, p, start..bounds[mid], Breakpoint::Normal, lines.last());
+            if width.fits(prefix.width) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        // A single grapheme can't be broken any further.
+        let Some(&split) = bounds.get(lo.saturating_sub(1)) else { break };
+
+        let prefix = line(engine, p, start..split, Breakpoint::Normal, lines.last());
+        lines.push(prefix);
+        start = split;
+        attempt = line(engine, p, start..end, breakpoint, lines.last());
+    }
+
+    attempt
+}
+
 /// Runs the normal Knuth-Plass algorithm, but instead of building proper lines
 /// (which is costly) to determine costs, it determines approximate costs using
 /// cummulative arrays.
@@ -384,7 +782,12 @@ fn linebreak_optimized_approximate(
 
             // Whether the line is justified. This is not 100% accurate w.r.t
             // to line()'s behaviour, but good enough.
-            let justify = p.justify && !at_end && breakpoint != Breakpoint::Mandatory;
+            let justify = p.justify
+                && if at_end {
+                    p.justify_last
+                } else {
+                    breakpoint != Breakpoint::Mandatory
+                };
 
             // We don't really know whether the line naturally ends with a dash
             // here, so we can miss that case, but it's ok, since all of this
@@ -407,6 +810,7 @@ fn linebreak_optimized_approximate(
                         Abs::zero()
                     },
                 estimates.stretchability.estimate(start..trimmed_end),
+                Abs::zero(),
                 estimates.shrinkability.estimate(start..trimmed_end),
                 estimates.justifiables.estimate(start..trimmed_end),
             );
@@ -486,9 +890,9 @@ fn linebreak_optimized_approximate(
             unbreakable,
         );
 
+        exact += line_cost;
         pred = attempt;
         start = end;
-        exact += line_cost;
     }
 
     exact
@@ -511,6 +915,7 @@ fn ratio_and_cost(
         available_width,
         attempt.width,
         attempt.stretchability(),
+        attempt.spacing_stretchability(),
         attempt.shrinkability(),
         attempt.justifiables(),
     );
@@ -529,18 +934,70 @@ fn ratio_and_cost(
     (ratio, cost)
 }
 
+/// Compute the cost of the rivers that a justified line forms with its
+/// predecessor.
+///
+/// A river is a run of whitespace that flows vertically through consecutive
+/// lines. We detect it by counting the gaps between words in the line that lie
+/// almost directly below a gap in the previous line.
+fn river_cost(metrics: &CostMetrics, width: Abs, pred: &Line, attempt: &Line) -> Cost {
+    if metrics.river_cost <= 0.0 || !attempt.justify {
+        return 0.0;
+    }
+
+    let above = pred.gaps(width);
+    if above.is_empty() {
+        return 0.0;
+    }
+
+    let count = attempt
+        .gaps(width)
+        .into_iter()
+        .filter(|&x| above.iter().any(|&y| (x - y).abs() < metrics.river_distance))
+        .count();
+
+    metrics.river_cost * count as f64
+}
+
+/// Determine the badness of a justified line, which is a hundred times the
+/// cube of its stretch ratio like in TeX.
+///
+/// Returns `None` for lines that aren't justified or don't need to stretch.
+pub fn badness(p: &Preparation, line: &Line, width: Abs) -> Option<f64> {
+    if !line.justify {
+        return None;
+    }
+
+    let ratio = raw_ratio(
+        p,
+        width,
+        line.width,
+        line.stretchability(),
+        line.spacing_stretchability(),
+        line.shrinkability(),
+        line.justifiables(),
+    );
+
+    (ratio > 0.0).then(|| 100.0 * ratio.powi(3))
+}
+
 /// Determine the stretch ratio for a line given raw metrics.
 fn raw_ratio(
     p: &Preparation,
     available_width: Abs,
     line_width: Abs,
     stretchability: Abs,
+    spacing_stretchability: Abs,
     shrinkability: Abs,
     justifiables: usize,
 ) -> f64 {
     // Determine how much the line's spaces would need to be stretched
-    // to make it the desired width.
-    let delta = available_width - line_width;
+    // to make it the desired width. Stretchable spacing absorbs any extra
+    // space first, so the spaces only need to take up what is left.
+    let mut delta = available_width - line_width;
+    if delta > Abs::zero() {
+        delta = (delta - spacing_stretchability).max(Abs::zero());
+    }
 
     // Determine how much stretch is permitted.
     let adjust = if delta >= Abs::zero() { stretchability } else { shrinkability };
@@ -619,6 +1076,11 @@ fn raw_cost(
         cost += CONSECUTIVE_DASH_COST;
     }
 
+    // Penalize justified lines that are looser than the tolerance permits.
+    if justify && ratio > metrics.max_ratio {
+        cost += LOOSE_COST;
+    }
+
     cost
 }
 
@@ -647,16 +1109,35 @@ fn breakpoints(p: &Preparation, mut f: impl FnMut(usize, Breakpoint)) {
         _ => &SEGMENTER,
     };
 
+    // Find the start of the paragraph's last word if it must not be
+    // hyphenated.
+    let last_word = if hyphenate && !p.hyphenate_last_word {
+        text.split_word_bound_indices()
+            .rfind(|(_, segment)| is_word(segment))
+            .map(|(offset, _)| offset)
+    } else {
+        None
+    };
+
     let mut last = 0;
+    let mut link_end = 0;
     let mut iter = segmenter.segment_str(text).peekable();
 
     loop {
-        // Special case for links. UAX #14 doesn't handle them well.
+        // Special case for links and file paths. UAX #14 doesn't handle them
+        // well and they must not be hyphenated.
         let (head, tail) = text.split_at(last);
-        if head.ends_with("://") || tail.starts_with("www.") {
-            let (link, _) = link_prefix(tail);
+        let (link, _) = link_prefix(tail);
+        if last >= link_end
+            && (head.ends_with("://") || tail.starts_with("www.") || is_path(link))
+        {
             let end = last + link.len();
-            linebreak_link(link, |i| f(last + i, Breakpoint::Normal));
+            link_end = end;
+            linebreak_link(link, |i| {
+                if !nobreak_at(p, last + i) {
+                    f(last + i, Breakpoint::Normal);
+                }
+            });
             while iter.peek().is_some_and(|&p| p < end) {
                 iter.next();
             }
@@ -678,6 +1159,8 @@ fn breakpoints(p: &Preparation, mut f: impl FnMut(usize, Breakpoint)) {
             match lb.get(c) {
                 // Fix for: https://github.com/unicode-org/icu4x/issues/4146
                 LineBreak::Glue | LineBreak::WordJoiner | LineBreak::ZWJ => continue,
+                // Fixed-width spaces bind like non-breaking spaces.
+                _ if is_fixed_space(c) => continue,
                 LineBreak::MandatoryBreak
                 | LineBreak::CarriageReturn
                 | LineBreak::LineFeed
@@ -686,11 +1169,27 @@ fn breakpoints(p: &Preparation, mut f: impl FnMut(usize, Breakpoint)) {
             }
         };
 
-        // Hyphenate between the last and current breakpoint.
-        if hyphenate {
+        // Filter out break opportunities within text that must not break or
+        // that would separate punctuation from its word.
+        if breakpoint == Breakpoint::Normal
+            && (nobreak_at(p, point) || nobreak_punct(p, point))
+        {
+            continue;
+        }
+
+        // Hyphenate between the last and current breakpoint and break
+        // identifiers at their inner boundaries.
+        if hyphenate || p.break_identifiers {
             let mut offset = last;
             for segment in text[last..point].split_word_bounds() {
-                if !segment.is_empty() && segment.chars().all(char::is_alphabetic) {
+                if offset >= link_end && p.break_identifiers && is_identifier(segment) {
+                    identifier_breaks(p, offset, segment, &mut f);
+                } else if hyphenate
+                    && offset >= link_end
+                    && is_word(segment)
+                    && last_word != Some(offset)
+                    && (p.hyphenate_capitalized || !is_capitalized(segment))
+                {
                     hyphenations(p, &lb, offset, segment, &mut f);
                 }
                 offset += segment.len();
@@ -704,6 +1203,84 @@ fn breakpoints(p: &Preparation, mut f: impl FnMut(usize, Breakpoint)) {
     }
 }
 
+/// Whether a link-like piece of text is a file path.
+///
+/// Only text that starts like a path (`/`, `./`, `../`, `~/` or a drive
+/// prefix like `C:\`) and has at least two separators counts, so that words
+/// like "and/or" or "km/h" are still treated as regular text.
+fn is_path(text: &str) -> bool {
+    let is_separator = |c: char| matches!(c, '/' | '\\');
+    let drive = |text: &str| {
+        let mut chars = text.chars();
+        let valid = chars.next()?.is_ascii_alphabetic()
+            && chars.next()? == ':'
+            && chars.next().is_some_and(is_separator);
+        valid.then(|| &text[3..])
+    };
+
+    // The rest after the prefix's separator.
+    let Some(rest) = ["./", "../", "~/", "/"]
+        .into_iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+        .or_else(|| drive(text))
+    else {
+        return false;
+    };
+
+    !text.contains("://") && rest.chars().any(is_separator)
+}
+
+/// Whether a word-bounded segment is a word that can be hyphenated.
+fn is_word(segment: &str) -> bool {
+    !segment.is_empty() && segment.chars().all(char::is_alphabetic)
+}
+
+/// Whether a word starts with an uppercase letter.
+fn is_capitalized(word: &str) -> bool {
+    word.chars().next().is_some_and(char::is_uppercase)
+}
+
+/// Whether a word-bounded segment looks like a `camelCase` or `snake_case`
+/// identifier.
+fn is_identifier(segment: &str) -> bool {
+    if !segment.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return false;
+    }
+
+    let inner = segment.trim_matches('_');
+    inner.contains('_')
+        || (inner.starts_with(char::is_lowercase)
+            && inner
+                .chars()
+                .zip(inner.chars().skip(1))
+                .any(|(a, b)| a.is_lowercase() && b.is_uppercase()))
+}
+
+/// Generate breakpoints within an identifier: before an uppercase letter that
+/// follows a lowercase one and after underscores. The parts on both sides of
+/// a break must have at least two characters other than underscores.
+fn identifier_breaks(
+    p: &Preparation,
+    offset: usize,
+    identifier: &str,
+    mut f: impl FnMut(usize, Breakpoint),
+) {
+    let len = |part: &str| part.chars().filter(|&c| c != '_').count();
+    let mut start = 0;
+    let mut chars = identifier.char_indices().peekable();
+    while let Some((_, prev)) = chars.next() {
+        let Some(&(i, next)) = chars.peek() else { break };
+        let boundary =
+            (prev.is_lowercase() && next.is_uppercase()) || (prev == '_' && next != '_');
+        if boundary && len(&identifier[start..i]) >= 2 && len(&identifier[i..]) >= 2 {
+            if !nobreak_at(p, offset + i) {
+                f(offset + i, Breakpoint::Normal);
+            }
+            start = i;
+        }
+    }
+}
+
 /// Generate breakpoints for hyphenations within a word.
 fn hyphenations(
     p: &Preparation,
@@ -715,17 +1292,43 @@ fn hyphenations(
     let Some(lang) = lang_at(p, offset) else { return };
     let end = offset + word.len();
 
-    for syllable in hypher::hyphenate(word, lang) {
+    // Use the explicit hyphenation points of an exception if there is one
+    // and the language's patterns otherwise.
+    let syllables: Vec<&str> = match p.hyphenate_exceptions.get(lang, word) {
+        Some(breaks) => split_chars(word, &breaks),
+        None => {
+            let Some(lang) = hypher_lang(lang) else { return };
+            hypher::hyphenate(word, lang).collect()
+        }
+    };
+
+    // The number of characters before and after the current syllable boundary.
+    let mut before = 0;
+    let mut after = word.chars().count();
+
+    for syllable in syllables {
         offset += syllable.len();
 
+        let count = syllable.chars().count();
+        before += count;
+        after -= count;
+
         // Don't hyphenate after the final syllable.
         if offset == end {
             continue;
         }
 
+        // Filter out hyphenation opportunities that would leave too few
+        // characters on either side of the break.
+        if p.hyphenate_before.is_custom_and(|min| before < min)
+            || p.hyphenate_after.is_custom_and(|min| after < min)
+        {
+            continue;
+        }
+
         // Filter out hyphenation opportunities where hyphenation was actually
-        // disabled.
-        if !hyphenate_at(p, offset) {
+        // disabled or breaks are prevented altogether.
+        if !hyphenate_at(p, offset) || nobreak_at(p, offset) {
             continue;
         }
 
@@ -811,24 +1414,66 @@ fn hyphenate_at(p: &Preparation, offset: usize) -> bool {
         .unwrap_or(false)
 }
 
+/// Whether line breaks are prevented at the given offset, which is the case
+/// if the text on both sides of it must not break.
+fn nobreak_at(p: &Preparation, offset: usize) -> bool {
+    p.nobreak.unwrap_or_else(|| {
+        let nobreak = |offset| {
+            let (_, item) = p.get(offset);
+            item.text().is_some_and(|text| TextElem::nobreak_in(text.styles))
+        };
+        offset > 0 && nobreak(offset - 1) && nobreak(offset)
+    })
+}
+
+/// Whether a break at the given offset would separate punctuation from the
+/// word it belongs to.
+fn nobreak_punct(p: &Preparation, offset: usize) -> bool {
+    let before = p.text[..offset].trim_end().chars().next_back();
+    let after = p.text[offset..].chars().next();
+    before.is_some_and(|c| NOBREAK_AFTER.contains(&c) || p.nobreak_after.contains(&c))
+        || after
+            .is_some_and(|c| NOBREAK_BEFORE.contains(&c) || p.nobreak_before.contains(&c))
+}
+
 /// The text language at the given offset.
-fn lang_at(p: &Preparation, offset: usize) -> Option<hypher::Lang> {
-    let lang = p.lang.or_else(|| {
+fn lang_at(p: &Preparation, offset: usize) -> Option<Lang> {
+    p.lang.or_else(|| {
         let (_, item) = p.get(offset);
         let styles = item.text()?.styles;
         Some(TextElem::lang_in(styles))
-    })?;
+    })
+}
 
+/// The hyphenation patterns for a language, if there are any.
+fn hypher_lang(lang: Lang) -> Option<hypher::Lang> {
     let bytes = lang.as_str().as_bytes().try_into().ok()?;
     hypher::Lang::from_iso(bytes)
 }
 
+/// Split a word before the given character positions.
+fn split_chars<'w>(word: &'w str, breaks: &[usize]) -> Vec<&'w str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    for (i, (offset, _)) in word.char_indices().enumerate() {
+        if i > 0 && breaks.contains(&i) {
+            parts.push(&word[start..offset]);
+            start = offset;
+        }
+    }
+    parts.push(&word[start..]);
+    parts
+}
+
 /// Resolved metrics relevant for cost computation.
 struct CostMetrics {
     min_ratio: f64,
     min_approx_ratio: f64,
+    max_ratio: f64,
     hyph_cost: Cost,
     runt_cost: Cost,
+    river_cost: Cost,
+    river_distance: Abs,
     approx_hyphen_width: Abs,
 }
 
@@ -839,8 +1484,16 @@ impl CostMetrics {
             // When justifying, we may stretch spaces below their natural width.
             min_ratio: if p.justify { MIN_RATIO } else { 0.0 },
             min_approx_ratio: if p.justify { MIN_APPROX_RATIO } else { 0.0 },
+            // Lines may only stretch up to the tolerated badness.
+            max_ratio: match p.tolerance {
+                Some(tolerance) if p.justify => (tolerance / 100.0).cbrt(),
+                _ => f64::INFINITY,
+            },
             hyph_cost: DEFAULT_HYPH_COST * p.costs.hyphenation().get(),
             runt_cost: DEFAULT_RUNT_COST * p.costs.runt().get(),
+            river_cost: DEFAULT_RIVER_COST * p.costs.river().get(),
+            // Gaps closer than this are considered to be part of a river.
+            river_distance: Em::new(0.3).at(p.size),
             // Approximate hyphen width for estimates.
             approx_hyphen_width: Em::new(0.33).at(p.size),
         }
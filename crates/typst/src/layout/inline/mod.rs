@@ -6,21 +6,24 @@ mod prepare;
 mod shaping;
 
 use comemo::{Track, Tracked, TrackedMut};
+use ecow::EcoString;
 
 use self::collect::{collect, Item, Segment, SpanMapper};
-use self::finalize::finalize;
-use self::line::{commit, line, Line};
-use self::linebreak::{linebreak, Breakpoint};
+use self::finalize::{finalize, narrow, paragraph_width, with_first_line};
+use self::line::{commit, glyph_extents, line, restyle, Dash, Line, SHY};
+use self::linebreak::{
+    badness, intrinsic_widths, linebreak, trim_ignorables, Breakpoint,
+};
 use self::prepare::{prepare, Preparation};
 use self::shaping::{
-    cjk_punct_style, is_of_cj_script, shape_range, ShapedGlyph, ShapedText,
-    BEGIN_PUNCT_PAT, END_PUNCT_PAT,
+    cjk_punct_style, is_fixed_space, is_of_cj_script, shape, shape_range, ShapedGlyph,
+    ShapedText, BEGIN_PUNCT_PAT, END_PUNCT_PAT,
 };
-use crate::diag::SourceResult;
+use crate::diag::{warning, SourceResult};
 use crate::engine::{Engine, Route, Sink, Traced};
-use crate::foundations::StyleChain;
+use crate::foundations::{Repr, Smart, StyleChain};
 use crate::introspection::{Introspector, Locator, LocatorLink};
-use crate::layout::{Fragment, Size};
+use crate::layout::{Abs, Dir, Fr, Fragment, Frame, Size};
 use crate::model::ParElem;
 use crate::realize::StyleVec;
 use crate::World;
@@ -28,6 +31,93 @@ use crate::World;
 /// Range of a substring of text.
 type Range = std::ops::Range<usize>;
 
+/// Information about a line that resulted from breaking a paragraph.
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct LineInfo {
+    /// The byte range of the line in the paragraph's collected text.
+    pub range: Range,
+    /// The word that is hyphenated at the end of the line, if any.
+    pub hyphenation: Option<Hyphenation>,
+    /// The natural width of the line.
+    pub width: Abs,
+    /// How far the line extends beyond the available width, even after
+    /// shrinking it as much as possible. Zero for lines that fit.
+    pub overflow: Abs,
+    /// The largest ascender of the fonts in the line.
+    pub ascent: Abs,
+    /// The largest descender of the fonts in the line, as a positive length.
+    pub descent: Abs,
+    /// The largest x-height of the fonts in the line.
+    pub x_height: Abs,
+    /// The largest cap-height of the fonts in the line.
+    pub cap_height: Abs,
+}
+
+/// The measurements of a trial line, determined without building its frame.
+#[derive(Debug, Copy, Clone, PartialEq, Hash)]
+pub struct LineMetrics {
+    /// The natural width of the line.
+    pub width: Abs,
+    /// The height of the line's frame.
+    pub height: Abs,
+    /// The distance from the top of the line's frame to its baseline.
+    pub baseline: Abs,
+    /// The sum of the fractions of the line's fractional spacing and boxes.
+    pub fr: Fr,
+}
+
+/// Where a glyph ends up in the frame of its line.
+#[derive(Debug, Copy, Clone, PartialEq, Hash)]
+pub struct GlyphExtent {
+    /// The horizontal offset of the glyph's left edge.
+    pub x: Abs,
+    /// The glyph's advance, including justification.
+    pub width: Abs,
+    /// The vertical offset of the top edge of the glyph's text.
+    pub top: Abs,
+    /// The vertical offset of the bottom edge of the glyph's text.
+    pub bottom: Abs,
+}
+
+/// A word that was hyphenated at the end of a line.
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct Hyphenation {
+    /// The hyphenated word.
+    pub word: EcoString,
+    /// The byte range of the word in the paragraph's collected text.
+    pub range: Range,
+    /// The byte offset within the word at which it was broken. If the word
+    /// was broken at a soft hyphen, the hyphen follows this offset.
+    pub split: usize,
+}
+
+/// A run of a paragraph's text with the same bidirectional embedding level.
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct BidiRun {
+    /// The byte range of the run in the paragraph's collected text.
+    pub range: Range,
+    /// The resolved embedding level of the run. Even levels are set
+    /// left-to-right and odd levels right-to-left.
+    pub level: u8,
+    /// The direction in which the run is set.
+    pub dir: Dir,
+    /// The base direction of the BiDi paragraph that contains the run.
+    pub base: Dir,
+}
+
+/// The frames of a paragraph's lines.
+#[derive(Debug, Clone)]
+pub(crate) struct LineFrames {
+    /// One frame per line.
+    pub frames: Vec<Frame>,
+    /// The leading between each pair of consecutive lines.
+    pub leading: Vec<Abs>,
+    /// Whether each line but the last must be kept in the same region as the
+    /// line after it, to prevent orphans and widows or to keep the paragraph
+    /// together.
+    pub sticky: Vec<bool>,
+}
+
 /// Layouts content inline.
 pub(crate) fn layout_inline(
     children: &StyleVec,
@@ -37,7 +127,355 @@ pub(crate) fn layout_inline(
     consecutive: bool,
     region: Size,
     expand: bool,
-) -> SourceResult<Fragment> {
+) -> SourceResult<LineFrames> {
+    layout_inline_cached(children, engine, locator, styles, consecutive, region, expand)
+        .map(|(lines, _)| lines)
+}
+
+/// Layouts content inline and additionally returns where its lines were
+/// broken.
+///
+/// Since the layout is memoized, calling this after [`layout_inline`] with the
+/// same arguments does not lay out the content again.
+pub(crate) fn layout_inline_with_lines(
+    children: &StyleVec,
+    engine: &mut Engine,
+    locator: Locator,
+    styles: StyleChain,
+    consecutive: bool,
+    region: Size,
+    expand: bool,
+) -> SourceResult<(LineFrames, Vec<LineInfo>)> {
+    layout_inline_cached(children, engine, locator, styles, consecutive, region, expand)
+}
+
+/// Measures the intrinsic widths of inline content.
+///
+/// Returns the width of its widest unbreakable piece (min-content) and the
+/// width it takes up when it is never broken (max-content).
+pub(crate) fn measure_inline(
+    children: &StyleVec,
+    engine: &mut Engine,
+    locator: Locator,
+    styles: StyleChain,
+    consecutive: bool,
+    region: Size,
+) -> SourceResult<(Abs, Abs)> {
+    let query = Query::IntrinsicWidths;
+    match query_inline(children, engine, locator, styles, consecutive, region, query)? {
+        Answer::IntrinsicWidths(min, max) => Ok((min, max)),
+        _ => unreachable!(),
+    }
+}
+
+/// Determines the bidirectional structure of a range of the collected text of
+/// inline content.
+///
+/// Returns the runs of the range with the same embedding level, in logical
+/// order.
+pub(crate) fn analyze_inline_bidi(
+    children: &StyleVec,
+    engine: &mut Engine,
+    locator: Locator,
+    styles: StyleChain,
+    consecutive: bool,
+    region: Size,
+    range: Range,
+) -> SourceResult<Vec<BidiRun>> {
+    let query = Query::BidiRuns(range);
+    match query_inline(children, engine, locator, styles, consecutive, region, query)? {
+        Answer::BidiRuns(runs) => Ok(runs),
+        _ => unreachable!(),
+    }
+}
+
+/// Measures trial lines spanning ranges of the collected text of inline
+/// content, without building their frames.
+///
+/// Each line is measured as if it was broken at the end of its range.
+pub(crate) fn measure_inline_lines(
+    children: &StyleVec,
+    engine: &mut Engine,
+    locator: Locator,
+    styles: StyleChain,
+    consecutive: bool,
+    region: Size,
+    ranges: Vec<Range>,
+) -> SourceResult<Vec<LineMetrics>> {
+    let query = Query::LineMetrics(ranges);
+    match query_inline(children, engine, locator, styles, consecutive, region, query)? {
+        Answer::LineMetrics(metrics) => Ok(metrics),
+        _ => unreachable!(),
+    }
+}
+
+/// Determines where the glyphs for a range of the collected text of inline
+/// content end up.
+///
+/// Returns the extents of the glyphs in the frame of each line, in the order
+/// of the lines and in visual order within each line.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn locate_inline_glyphs(
+    children: &StyleVec,
+    engine: &mut Engine,
+    locator: Locator,
+    styles: StyleChain,
+    consecutive: bool,
+    region: Size,
+    expand: bool,
+    range: Range,
+) -> SourceResult<Vec<Vec<GlyphExtent>>> {
+    let query = Query::GlyphExtents(range, expand);
+    match query_inline(children, engine, locator, styles, consecutive, region, query)? {
+        Answer::GlyphExtents(extents) => Ok(extents),
+        _ => unreachable!(),
+    }
+}
+
+/// A question about inline content that can be answered from its prepared
+/// text, without building the frames of its lines.
+#[derive(Debug, Clone, Hash)]
+enum Query {
+    /// The min- and max-content widths.
+    IntrinsicWidths,
+    /// The bidirectional runs of a range of the text.
+    BidiRuns(Range),
+    /// The metrics of trial lines spanning ranges of the text.
+    LineMetrics(Vec<Range>),
+    /// The extents of the glyphs of a range of the text in each line, and
+    /// whether the lines are expanded to the full region.
+    GlyphExtents(Range, bool),
+}
+
+/// The answer to a [`Query`] of the same kind.
+#[derive(Debug, Clone)]
+enum Answer {
+    IntrinsicWidths(Abs, Abs),
+    BidiRuns(Vec<BidiRun>),
+    LineMetrics(Vec<LineMetrics>),
+    GlyphExtents(Vec<Vec<GlyphExtent>>),
+}
+
+/// Prepares inline content and answers a query about it.
+///
+/// All queries share this memoized function, so they only differ in what
+/// they compute from the prepared content.
+fn query_inline(
+    children: &StyleVec,
+    engine: &mut Engine,
+    locator: Locator,
+    styles: StyleChain,
+    consecutive: bool,
+    region: Size,
+    query: Query,
+) -> SourceResult<Answer> {
+    #[comemo::memoize]
+    #[allow(clippy::too_many_arguments)]
+    fn cached(
+        children: &StyleVec,
+        world: Tracked<dyn World + '_>,
+        introspector: Tracked<Introspector>,
+        traced: Tracked<Traced>,
+        sink: TrackedMut<Sink>,
+        route: Tracked<Route>,
+        locator: Tracked<Locator>,
+        styles: StyleChain,
+        consecutive: bool,
+        region: Size,
+        query: Query,
+    ) -> SourceResult<Answer> {
+        let link = LocatorLink::new(locator);
+        let locator = Locator::link(&link);
+        let mut engine = Engine {
+            world,
+            introspector,
+            traced,
+            sink,
+            route: Route::extend(route),
+        };
+
+        let (text, segments, spans) =
+            collect(children, &mut engine, locator, &styles, region, consecutive)?;
+        let p = prepare(&mut engine, children, &text, segments, spans, styles)?;
+
+        Ok(match query {
+            Query::IntrinsicWidths => {
+                let (min, max) = intrinsic_widths(&engine, &p);
+                Answer::IntrinsicWidths(min, max)
+            }
+            Query::BidiRuns(range) => Answer::BidiRuns(p.bidi_runs(range)),
+            Query::LineMetrics(ranges) => Answer::LineMetrics(
+                ranges
+                    .into_iter()
+                    .map(|range| measure_line(&mut engine, &p, styles, range))
+                    .collect::<SourceResult<_>>()?,
+            ),
+            Query::GlyphExtents(range, expand) => Answer::GlyphExtents(locate_glyphs(
+                &mut engine,
+                &p,
+                styles,
+                region,
+                expand,
+                range,
+            )?),
+        })
+    }
+
+    cached(
+        children,
+        engine.world,
+        engine.introspector,
+        engine.traced,
+        TrackedMut::reborrow_mut(&mut engine.sink),
+        engine.route.track(),
+        locator.track(),
+        styles,
+        consecutive,
+        region,
+        query,
+    )
+}
+
+/// Measure a trial line spanning a range of the prepared text.
+///
+/// The range is clipped to the text and widened to the nearest character
+/// boundaries. A line at the start of the text is set with the paragraph's
+/// first-line styles, like the first line of its frames.
+fn measure_line(
+    engine: &mut Engine,
+    p: &Preparation,
+    styles: StyleChain,
+    range: Range,
+) -> SourceResult<LineMetrics> {
+    let mut end = range.end.min(p.text.len());
+    while !p.text.is_char_boundary(end) {
+        end += 1;
+    }
+    let mut start = range.start.min(end);
+    while !p.text.is_char_boundary(start) {
+        start -= 1;
+    }
+
+    let breakpoint = if end == p.text.len() || p.text[..end].ends_with('\n') {
+        Breakpoint::Mandatory
+    } else {
+        Breakpoint::Normal
+    };
+
+    let measure = |engine: &mut Engine, line: &Line| {
+        let (top, bottom) = line.measure(engine);
+        LineMetrics {
+            width: line.width,
+            height: top + bottom,
+            baseline: top,
+            fr: line.fr(),
+        }
+    };
+
+    let line = line(engine, p, start..end, breakpoint, None);
+    if start == 0 {
+        if let Some(metrics) = with_first_line(engine, p, &line, styles, measure)? {
+            return Ok(metrics);
+        }
+    }
+
+    Ok(measure(engine, &line))
+}
+
+/// Determine the extents of the glyphs of a range of the prepared text in
+/// each line, the same way as the lines' frames are built.
+fn locate_glyphs(
+    engine: &mut Engine,
+    p: &Preparation,
+    styles: StyleChain,
+    region: Size,
+    expand: bool,
+    range: Range,
+) -> SourceResult<Vec<Vec<GlyphExtent>>> {
+    let narrowed = narrow(p, region);
+    let inner = narrowed.map_or(region, |(inner, _)| inner);
+    let lines = linebreak(engine, p, inner.x - p.hang);
+
+    let (width, offset) = match narrowed {
+        Some((inner, offset)) => (inner.x, offset),
+        None => (paragraph_width(p, &lines, region, expand), Abs::zero()),
+    };
+    // The first line's baseline may be moved to a fixed distance from the top
+    // of its frame.
+    let shrink = ParElem::shrink_in(styles);
+    let first_baseline = ParElem::first_baseline_in(styles);
+    let locate = |engine: &mut Engine, line: &Line, first: bool| {
+        let baseline = match first_baseline {
+            Smart::Custom(distance) if first => distance,
+            _ => line.measure(engine).0,
+        };
+        let mut extents =
+            glyph_extents(engine, p, line, width, shrink, baseline, range.clone());
+        for extent in &mut extents {
+            extent.x += offset;
+        }
+        extents
+    };
+
+    let mut extents = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        let first = if i == 0 {
+            with_first_line(engine, p, line, styles, |engine, line| {
+                locate(engine, line, true)
+            })?
+        } else {
+            None
+        };
+        extents.push(first.unwrap_or_else(|| locate(engine, line, i == 0)));
+    }
+
+    Ok(extents)
+}
+
+/// Determine the word that is hyphenated at the end of a line, if any.
+fn hyphenation(p: &Preparation, line: &Line) -> Option<Hyphenation> {
+    if line.dash != Some(Dash::Soft) {
+        return None;
+    }
+
+    Some(hyphenated_word(p.text, line.range.end))
+}
+
+/// Determine the word around a hyphenation break at the given offset.
+///
+/// A soft hyphen at which the line was broken is part of the word, but the
+/// split lies before it.
+fn hyphenated_word(text: &str, end: usize) -> Hyphenation {
+    let is_letter = |c: &char| c.is_alphabetic() || *c == SHY;
+    let split = end - text[..end].strip_suffix(SHY).map_or(0, |_| SHY.len_utf8());
+    let before: usize = text[..split]
+        .chars()
+        .rev()
+        .take_while(is_letter)
+        .map(char::len_utf8)
+        .sum();
+    let after: usize =
+        text[end..].chars().take_while(is_letter).map(char::len_utf8).sum();
+
+    let range = split - before..end + after;
+    Hyphenation {
+        word: text[range.clone()].into(),
+        range,
+        split: before,
+    }
+}
+
+/// Layouts content inline, returning the frames of the lines and where they
+/// were broken.
+fn layout_inline_cached(
+    children: &StyleVec,
+    engine: &mut Engine,
+    locator: Locator,
+    styles: StyleChain,
+    consecutive: bool,
+    region: Size,
+    expand: bool,
+) -> SourceResult<(LineFrames, Vec<LineInfo>)> {
     #[comemo::memoize]
     #[allow(clippy::too_many_arguments)]
     fn cached(
@@ -52,7 +490,7 @@ pub(crate) fn layout_inline(
         consecutive: bool,
         region: Size,
         expand: bool,
-    ) -> SourceResult<Fragment> {
+    ) -> SourceResult<(LineFrames, Vec<LineInfo>)> {
         let link = LocatorLink::new(locator);
         let locator = Locator::link(&link);
         let mut engine = Engine {
@@ -70,11 +508,63 @@ pub(crate) fn layout_inline(
         // Perform BiDi analysis and then prepares paragraph layout.
         let p = prepare(&mut engine, children, &text, segments, spans, styles)?;
 
-        // Break the paragraph into lines.
-        let lines = linebreak(&engine, &p, region.x - p.hang);
+        // Break the paragraph into lines, in its own width if it has one.
+        let inner = narrow(&p, region).map_or(region, |(inner, _)| inner);
+        let width = inner.x - p.hang;
+        let lines = linebreak(&engine, &p, width);
+
+        // Record where the lines were broken and whether they overflow. The
+        // first line is measured with its first-line styles, as it is built.
+        let shrink = ParElem::shrink_in(styles);
+        let record = |_: &mut Engine, line: &Line| {
+            let shrinkability = if shrink { line.shrinkability() } else { Abs::zero() };
+            LineInfo {
+                range: line.range.clone(),
+                hyphenation: hyphenation(&p, line),
+                width: line.width,
+                overflow: (line.width - shrinkability - width).max(Abs::zero()),
+                ascent: line.ascent(),
+                descent: line.descent(),
+                x_height: line.x_height(),
+                cap_height: line.cap_height(),
+            }
+        };
+
+        let mut info = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            let first = if i == 0 {
+                with_first_line(&mut engine, &p, line, styles, record)?
+            } else {
+                None
+            };
+            info.push(first.unwrap_or_else(|| record(&mut engine, line)));
+        }
+
+        // Report lines that are too loose or too wide.
+        let tolerance = p.tolerance;
+        let overfull = ParElem::overfull_tolerance_in(styles);
+        for (line, info) in lines.iter().zip(&info) {
+            let span = p.spans.span_at(line.range.start).0;
+            if let Some(badness) = badness(&p, line, width)
+                .filter(|&badness| tolerance.is_some_and(|max| badness > max))
+            {
+                engine.sink.warn(warning!(
+                    span, "underfull line (badness {})", badness.round();
+                    hint: "try hyphenating or rephrasing the line",
+                ));
+            }
+
+            if overfull.is_some_and(|max| info.overflow > max) {
+                engine.sink.warn(warning!(
+                    span, "overfull line ({} too wide)", info.overflow.repr();
+                    hint: "try hyphenating or rephrasing the line",
+                ));
+            }
+        }
 
         // Turn the selected lines into frames.
-        finalize(&mut engine, &p, &lines, styles, region, expand)
+        let frames = finalize(&mut engine, &p, &lines, styles, region, expand)?;
+        Ok((frames, info))
     }
 
     cached(
@@ -91,3 +581,27 @@ pub(crate) fn layout_inline(
         expand,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyphenated_word() {
+        // Broken by automatic hyphenation.
+        let text = "An extraordinary day";
+        let hyphenation = hyphenated_word(text, 8);
+        assert_eq!(hyphenation.word.as_str(), "extraordinary");
+        assert_eq!(hyphenation.range, 3..16);
+        assert_eq!(hyphenation.split, 5);
+
+        // Broken at an explicit soft hyphen, which is not counted in the
+        // split.
+        let text = "An ex\u{ad}tra\u{ad}ordinary day";
+        let end = text.rfind(SHY).unwrap() + SHY.len_utf8();
+        let hyphenation = hyphenated_word(text, end);
+        assert_eq!(hyphenation.word.as_str(), "ex\u{ad}tra\u{ad}ordinary");
+        assert_eq!(hyphenation.range, 3..text.len() - 4);
+        assert_eq!(hyphenation.split, "ex\u{ad}tra".len());
+    }
+}
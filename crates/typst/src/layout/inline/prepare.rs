@@ -1,10 +1,13 @@
+use std::num::NonZeroUsize;
+
 use unicode_bidi::{BidiInfo, Level as BidiLevel};
 
 use super::*;
 use crate::foundations::{Resolve, Smart};
-use crate::layout::{Abs, AlignElem, Dir, Em, FixedAlignment};
-use crate::model::Linebreaks;
+use crate::layout::{Abs, AlignElem, Dir, Em, FixedAlignment, Ratio, Rel};
+use crate::model::{HyphenationExceptions, Linebreaks, Overflow};
 use crate::text::{Costs, Lang, TextElem};
+use crate::utils::Numeric;
 
 /// A paragraph representation in which children are already layouted and text
 /// is already preshaped.
@@ -27,16 +30,55 @@ pub struct Preparation<'a> {
     pub spans: SpanMapper,
     /// Whether to hyphenate if it's the same for all children.
     pub hyphenate: Option<bool>,
+    /// Whether to prevent line breaks if it's the same for all children.
+    pub nobreak: Option<bool>,
     /// Costs for various layout decisions.
     pub costs: Costs,
     /// The dominant direction.
     pub dir: Dir,
     /// The text language if it's the same for all children.
     pub lang: Option<Lang>,
+    /// An empty text run in the paragraph's style, which gives the lines their
+    /// height if the paragraph consists only of spacing.
+    pub strut: Option<ShapedText<'a>>,
     /// The paragraph's resolved horizontal alignment.
     pub align: FixedAlignment,
+    /// The resolved horizontal alignment of the last line, if it differs.
+    pub last_line_align: Smart<FixedAlignment>,
+    /// The width in which the lines are set, if it differs from the region.
+    pub width: Smart<Rel<Abs>>,
     /// Whether to justify the paragraph.
     pub justify: bool,
+    /// Whether to also justify the paragraph's last line.
+    pub justify_last: bool,
+    /// How much word spaces may stretch at most when justifying.
+    pub justify_stretch: Option<Ratio>,
+    /// Whether to spread leftover space between glyphs when justifying.
+    pub justify_glyphs: bool,
+    /// Whether to justify Arabic text with kashidas.
+    pub justify_kashida: bool,
+    /// How much glyphs may be scaled horizontally when justifying.
+    pub justify_scale: Ratio,
+    /// The minimum number of characters before a hyphenation break.
+    pub hyphenate_before: Smart<usize>,
+    /// The minimum number of characters after a hyphenation break.
+    pub hyphenate_after: Smart<usize>,
+    /// The maximum number of consecutive hyphenated lines.
+    pub hyphenate_lines: Option<usize>,
+    /// How much of the width a ragged line may leave empty before hyphenating.
+    pub ragged_tolerance: Option<Ratio>,
+    /// Words with explicit hyphenation points.
+    pub hyphenate_exceptions: &'a HyphenationExceptions,
+    /// Additional characters before which lines must not be broken.
+    pub nobreak_before: &'a [char],
+    /// Additional characters after which lines must not be broken.
+    pub nobreak_after: &'a [char],
+    /// Whether the last word of the paragraph may be hyphenated.
+    pub hyphenate_last_word: bool,
+    /// Whether capitalized words may be hyphenated.
+    pub hyphenate_capitalized: bool,
+    /// Whether identifiers may be broken at their inner boundaries.
+    pub break_identifiers: bool,
     /// The paragraph's hanging indent.
     pub hang: Abs,
     /// Whether to add spacing between CJK and Latin characters.
@@ -45,8 +87,22 @@ pub struct Preparation<'a> {
     pub fallback: bool,
     /// The leading of the paragraph.
     pub leading: Abs,
+    /// The leading relative to the height of the line above, if any.
+    pub leading_ratio: Option<Ratio>,
+    /// Whether the leading scales with the text size of the line below.
+    pub leading_adaptive: bool,
     /// How to determine line breaks.
     pub linebreaks: Smart<Linebreaks>,
+    /// Whether to break overlong words at grapheme boundaries.
+    pub emergency_breaks: bool,
+    /// Whether to keep spaces at the end of lines.
+    pub keep_trailing_spaces: bool,
+    /// The badness up to which a justified line is acceptable.
+    pub tolerance: Option<f64>,
+    /// The maximum number of lines.
+    pub max_lines: Option<NonZeroUsize>,
+    /// What to do with lines that are wider than the paragraph.
+    pub overflow: Overflow,
     /// The text size.
     pub size: Abs,
 }
@@ -58,6 +114,22 @@ impl<'a> Preparation<'a> {
         &self.items[idx]
     }
 
+    /// The spacing below a line with the given height, followed by the given
+    /// line, if any.
+    pub fn leading_below(&self, height: Abs, next: Option<&Line>) -> Abs {
+        let leading = match self.leading_ratio {
+            Some(ratio) => ratio.of(height),
+            None => self.leading,
+        };
+
+        match next.and_then(Line::text_size) {
+            Some(size) if self.leading_adaptive && self.size > Abs::zero() => {
+                leading * (size / self.size)
+            }
+            _ => leading,
+        }
+    }
+
     /// Iterate over the items that intersect the given `sliced` range.
     pub fn slice(&self, sliced: Range) -> impl Iterator<Item = &(Range, Item<'a>)> {
         let start = self.indices.get(sliced.start).copied().unwrap_or(0);
@@ -65,6 +137,41 @@ impl<'a> Preparation<'a> {
             range.start < sliced.end || range.end <= sliced.end
         })
     }
+
+    /// Split the given range of the text into runs with the same embedding
+    /// level, each within a single BiDi paragraph.
+    pub fn bidi_runs(&self, range: Range) -> Vec<BidiRun> {
+        let end = range.end.min(self.text.len());
+        let Some(bidi) = &self.bidi else {
+            if range.start >= end {
+                return vec![];
+            }
+            let level = if self.dir == Dir::RTL { 1 } else { 0 };
+            let range = range.start..end;
+            return vec![BidiRun { range, level, dir: self.dir, base: self.dir }];
+        };
+
+        let mut runs = vec![];
+        for para in &bidi.paragraphs {
+            let base = if para.level.is_rtl() { Dir::RTL } else { Dir::LTR };
+            let mut cursor = range.start.max(para.range.start);
+            let end = end.min(para.range.end);
+            while cursor < end {
+                let level = bidi.levels[cursor];
+                let len =
+                    bidi.levels[cursor..end].iter().take_while(|&&l| l == level).count();
+                runs.push(BidiRun {
+                    range: cursor..cursor + len,
+                    level: level.number(),
+                    dir: if level.is_rtl() { Dir::RTL } else { Dir::LTR },
+                    base,
+                });
+                cursor += len;
+            }
+        }
+
+        runs
+    }
 }
 
 /// Performs BiDi analysis and then prepares paragraph layout by building a
@@ -116,11 +223,31 @@ pub fn prepare<'a>(
         indices.extend(range.clone().map(|_| i));
     }
 
+    add_tracking_between_runs(&mut items);
+
     let cjk_latin_spacing = TextElem::cjk_latin_spacing_in(styles).is_auto();
     if cjk_latin_spacing {
         add_cjk_latin_spacing(&mut items);
     }
 
+    // A paragraph without any text or inline content still takes up a line of
+    // the current font's height.
+    let spacing_only = items.iter().all(|(_, item)| {
+        matches!(
+            item,
+            Item::Absolute(..)
+                | Item::Stretchable(..)
+                | Item::Fractional(_, None)
+                | Item::Tag(_)
+                | Item::Skip(_)
+        )
+    });
+    let strut = spacing_only.then(|| {
+        let lang = TextElem::lang_in(styles);
+        let region = TextElem::region_in(styles);
+        shape(engine, "", 0..0, styles, dir, lang, region)
+    });
+
     Ok(Preparation {
         text,
         bidi: is_bidi.then_some(bidi),
@@ -128,20 +255,88 @@ pub fn prepare<'a>(
         indices,
         spans,
         hyphenate: children.shared_get(styles, TextElem::hyphenate_in),
+        nobreak: children.shared_get(styles, TextElem::nobreak_in),
         costs: TextElem::costs_in(styles),
         dir,
         lang: children.shared_get(styles, TextElem::lang_in),
+        strut,
         align: AlignElem::alignment_in(styles).resolve(styles).x,
+        last_line_align: ParElem::last_line_align_in(styles),
+        width: ParElem::width_in(styles),
         justify: ParElem::justify_in(styles),
+        justify_last: ParElem::justify_last_in(styles),
+        justify_stretch: ParElem::justify_stretch_in(styles),
+        justify_glyphs: ParElem::justify_glyphs_in(styles),
+        justify_kashida: ParElem::justify_kashida_in(styles),
+        justify_scale: ParElem::justify_scale_in(styles),
+        hyphenate_before: ParElem::hyphenate_before_in(styles),
+        hyphenate_after: ParElem::hyphenate_after_in(styles),
+        hyphenate_lines: ParElem::hyphenate_lines_in(styles),
+        ragged_tolerance: ParElem::ragged_tolerance_in(styles),
+        hyphenate_exceptions: ParElem::hyphenate_exceptions_in(styles),
+        nobreak_before: ParElem::nobreak_before_in(styles),
+        nobreak_after: ParElem::nobreak_after_in(styles),
+        hyphenate_last_word: ParElem::hyphenate_last_word_in(styles),
+        hyphenate_capitalized: ParElem::hyphenate_capitalized_in(styles),
+        break_identifiers: ParElem::break_identifiers_in(styles),
         hang: ParElem::hanging_indent_in(styles),
         cjk_latin_spacing,
         fallback: TextElem::fallback_in(styles),
         leading: ParElem::leading_in(styles),
+        leading_ratio: ParElem::leading_ratio_in(styles),
+        leading_adaptive: ParElem::leading_adaptive_in(styles),
         linebreaks: ParElem::linebreaks_in(styles),
+        emergency_breaks: ParElem::emergency_breaks_in(styles),
+        keep_trailing_spaces: ParElem::keep_trailing_spaces_in(styles),
+        tolerance: ParElem::tolerance_in(styles),
+        max_lines: ParElem::max_lines_in(styles),
+        overflow: ParElem::overflow_in(styles),
         size: TextElem::size_in(styles),
     })
 }
 
+/// Add tracking after the last glyph of a text run that is directly followed
+/// by another text run with the same tracking and direction.
+///
+/// Shaping applies tracking only between the glyphs of a single run, so
+/// without this, the boundary between two differently styled runs would lack
+/// the spacing.
+fn add_tracking_between_runs(items: &mut [(Range, Item)]) {
+    let mut items = items
+        .iter_mut()
+        .filter(|(_, x)| !matches!(x, Item::Tag(_)))
+        .peekable();
+
+    while let Some((_, item)) = items.next() {
+        let Some(text) = item.text_mut() else { continue };
+        let Some((_, Item::Text(next))) = items.peek() else { continue };
+
+        // Only continue tracking that is the same on both sides. If it
+        // changes at the boundary, it is unclear which one should win.
+        let tracking = TextElem::tracking_in(text.styles);
+        if tracking.is_zero()
+            || text.dir != next.dir
+            || tracking != TextElem::tracking_in(next.styles)
+        {
+            continue;
+        }
+
+        // The glyphs are in visual order, so the logically last glyph of an
+        // RTL run is the first one and its trailing side is on the left.
+        let ltr = text.dir.is_positive();
+        let glyphs = text.glyphs.to_mut();
+        let glyph = if ltr { glyphs.last_mut() } else { glyphs.first_mut() };
+        let Some(glyph) = glyph else { continue };
+
+        let amount = Em::from_length(tracking, text.size);
+        glyph.x_advance += amount;
+        if !ltr {
+            glyph.x_offset += amount;
+        }
+        text.width += tracking;
+    }
+}
+
 /// Add some spacing between Han characters and western characters. See
 /// Requirements for Chinese Text Layout, Section 3.2.2 Mixed Text Composition
 /// in Horizontal Written Mode
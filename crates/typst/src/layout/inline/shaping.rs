@@ -13,14 +13,26 @@ use unicode_script::{Script, UnicodeScript};
 use super::{Item, Range, SpanMapper};
 use crate::engine::Engine;
 use crate::foundations::{Smart, StyleChain};
-use crate::layout::{Abs, Dir, Em, Frame, FrameItem, Point, Size};
+use crate::layout::{
+    Abs, Dir, Em, Frame, FrameItem, GroupItem, Point, Ratio, Rel, Size, Transform,
+};
+use crate::model::ParElem;
 use crate::text::{
-    decorate, families, features, variant, Font, FontVariant, Glyph, Lang, Region,
-    TextElem, TextItem,
+    decorate, families, features, variant, Font, FontStyle, FontVariant, Glyph, Lang,
+    Region, TextElem, TextItem,
 };
-use crate::utils::SliceExt;
+use crate::utils::{Numeric, SliceExt};
+use crate::visualize::{FixedStroke, LineJoin};
 use crate::World;
 
+/// The stroke thickness with which glyphs are emboldened if the font lacks the
+/// requested weight.
+const SYNTHETIC_BOLD: Em = Em::new(0.04);
+
+/// The horizontal shear with which glyphs are slanted if the font lacks an
+/// italic style. Corresponds to an angle of about 14 degrees.
+const SYNTHETIC_SLANT: Ratio = Ratio::new(0.25);
+
 /// The result of shaping text.
 ///
 /// This type contains owned or borrowed shaped text runs, which can be
@@ -100,12 +112,34 @@ impl ShapedGlyph {
         is_space(self.c)
     }
 
+    /// Whether the glyph is a space that is stretched for justification.
+    pub fn is_justifiable_space(&self) -> bool {
+        is_justifiable_space(self.c)
+    }
+
     /// Whether the glyph is justifiable.
     pub fn is_justifiable(&self) -> bool {
         // GB style is not relevant here.
         self.is_justifiable
     }
 
+    /// How much of the extra space in an underfull line the glyph receives
+    /// relative to other justifiable glyphs.
+    ///
+    /// Spaces are weighted by their width relative to the font's normal space,
+    /// so that widened sentence spaces grow more and narrow spaces less.
+    pub fn justification_weight(&self) -> f64 {
+        if !is_justifiable_space(self.c) {
+            return 1.0;
+        }
+
+        let ttf = self.font.ttf();
+        ttf.glyph_index(' ')
+            .and_then(|id| self.font.advance(id.0))
+            .filter(|space| *space > Em::zero())
+            .map_or(1.0, |space| self.x_advance / space)
+    }
+
     /// Whether the glyph is part of Chinese or Japanese script (i.e. CJ, not CJK).
     pub fn is_cj_script(&self) -> bool {
         is_cj_script(self.c, self.script)
@@ -144,13 +178,17 @@ impl ShapedGlyph {
             || self.c.is_ascii_digit()
     }
 
-    pub fn base_adjustability(&self, style: CjkPunctStyle) -> Adjustability {
+    pub fn base_adjustability(
+        &self,
+        style: CjkPunctStyle,
+        space_shrink: Ratio,
+    ) -> Adjustability {
         let width = self.x_advance;
-        if self.is_space() {
+        if is_justifiable_space(self.c) {
             Adjustability {
-                // The number for spaces is from Knuth-Plass' paper
+                // The stretch number for spaces is from Knuth-Plass' paper
                 stretchability: (Em::zero(), width / 2.0),
-                shrinkability: (Em::zero(), width / 3.0),
+                shrinkability: (Em::zero(), width * space_shrink.get().clamp(0.0, 1.0)),
             }
         } else if self.is_cjk_left_aligned_punctuation(style) {
             Adjustability {
@@ -208,16 +246,24 @@ impl<'a> ShapedText<'a> {
     /// Build the shaped text's frame.
     ///
     /// The `justification` defines how much extra advance width each
-    /// [justifiable glyph](ShapedGlyph::is_justifiable) will get.
+    /// [justifiable glyph](ShapedGlyph::is_justifiable) will get. The
+    /// `extra_tracking` is added after each glyph cluster, except for the last
+    /// one if the text ends its line. The glyphs are scaled horizontally by
+    /// the `scale`.
+    #[allow(clippy::too_many_arguments)]
     pub fn build(
         &self,
         engine: &Engine,
         spans: &SpanMapper,
         justification_ratio: f64,
         extra_justification: Abs,
+        extra_tracking: Abs,
+        ends_line: bool,
+        kashidas: usize,
+        scale: f64,
     ) -> Frame {
         let (top, bottom) = self.measure(engine);
-        let size = Size::new(self.width, top + bottom);
+        let size = Size::new(self.width * scale, top + bottom);
 
         let mut offset = Abs::zero();
         let mut frame = Frame::soft(size);
@@ -228,6 +274,8 @@ impl<'a> ShapedText<'a> {
         let fill = TextElem::fill_in(self.styles);
         let stroke = TextElem::stroke_in(self.styles);
         let span_offset = TextElem::span_offset_in(self.styles);
+        let points = if kashidas > 0 { self.kashida_points() } else { vec![] };
+        let mut index = 0;
 
         for ((font, y_offset), group) in
             self.glyphs.as_ref().group_by_key(|g| (g.font.clone(), g.y_offset))
@@ -241,29 +289,25 @@ impl<'a> ShapedText<'a> {
             let pos = Point::new(offset, top + shift - y_offset.at(self.size));
             let glyphs: Vec<Glyph> = group
                 .iter()
-                .map(|shaped: &ShapedGlyph| {
-                    let adjustability_left = if justification_ratio < 0.0 {
-                        shaped.shrinkability().0
-                    } else {
-                        shaped.stretchability().0
-                    };
-                    let adjustability_right = if justification_ratio < 0.0 {
-                        shaped.shrinkability().1
-                    } else {
-                        shaped.stretchability().1
-                    };
-
-                    let justification_left = adjustability_left * justification_ratio;
-                    let mut justification_right =
-                        adjustability_right * justification_ratio;
-                    if shaped.is_justifiable() {
-                        justification_right +=
-                            Em::from_length(extra_justification, self.size)
-                    }
+                .flat_map(|shaped: &ShapedGlyph| {
+                    let point = points.iter().find(|&&(i, ..)| i == index);
+                    let (justification_left, justification_right) = self.justification(
+                        index,
+                        justification_ratio,
+                        extra_justification,
+                        extra_tracking,
+                        ends_line,
+                    );
+                    index += 1;
 
                     frame.size_mut().x += justification_left.at(self.size)
                         + justification_right.at(self.size);
 
+                    // The glyphs are scaled as a whole, so the justification
+                    // must be scaled inversely to keep its width.
+                    let justification_left = justification_left / scale;
+                    let justification_right = justification_right / scale;
+
                     // We may not be able to reach the offset completely if
                     // it exceeds u16, but better to have a roughly correct
                     // span offset than nothing.
@@ -288,47 +332,214 @@ impl<'a> ShapedText<'a> {
                     // D: justification_right
                     // A+B: Glyph's x_offset
                     // A+B+C+D: Glyph's x_advance
-                    Glyph {
+                    let range = (shaped.range.start - range.start).saturating_as()
+                        ..(shaped.range.end - range.start).saturating_as();
+                    let glyph = Glyph {
                         id: shaped.glyph_id,
                         x_advance: shaped.x_advance
                             + justification_left
                             + justification_right,
                         x_offset: shaped.x_offset + justification_left,
-                        range: (shaped.range.start - range.start).saturating_as()
-                            ..(shaped.range.end - range.start).saturating_as(),
+                        range,
                         span,
+                    };
+
+                    // Elongate the connection to the neighbouring letter with
+                    // kashidas if this is one of the line's kashida points.
+                    // Like inserted hyphens, they don't correspond to any text.
+                    let mut tatweels = vec![];
+                    if let Some(&(_, id, advance)) = point {
+                        frame.size_mut().x +=
+                            advance.at(self.size) * scale * kashidas as f64;
+                        let x_offset = Em::zero();
+                        let range = glyph.range.end..glyph.range.end;
+                        let tatweel =
+                            Glyph { id, x_advance: advance, x_offset, range, span };
+                        tatweels.resize(kashidas, tatweel);
                     }
+
+                    std::iter::once(glyph).chain(tatweels)
                 })
                 .collect();
 
+            let (embolden, slant) = self.synthesis(&font);
+            let stroke = stroke.clone().map(|s| s.unwrap_or_default()).or_else(|| {
+                (!embolden.is_zero()).then(|| FixedStroke {
+                    join: LineJoin::Round,
+                    ..FixedStroke::from_pair(fill.clone(), embolden.at(self.size))
+                })
+            });
+
             let item = TextItem {
                 font,
                 size: self.size,
                 lang: self.lang,
                 region: self.region,
                 fill: fill.clone(),
-                stroke: stroke.clone().map(|s| s.unwrap_or_default()),
+                stroke,
                 text: self.text[range.start - self.base..range.end - self.base].into(),
                 glyphs,
             };
 
+            // Slant and scale the glyphs around their baseline, together with
+            // their line decorations.
             let width = item.width();
-            if decos.is_empty() {
-                frame.push(pos, FrameItem::Text(item));
-            } else {
-                // Apply line decorations.
+            let transform = Transform::scale(Ratio::new(scale), Ratio::one())
+                .pre_concat(Transform::skew(-slant, Ratio::zero()));
+            if transform.is_identity() {
                 frame.push(pos, FrameItem::Text(item.clone()));
                 for deco in &decos {
                     decorate(&mut frame, deco, &item, width, shift, pos);
                 }
+            } else {
+                let mut inner = Frame::soft(Size::zero());
+                inner.push(Point::zero(), FrameItem::Text(item.clone()));
+                for deco in &decos {
+                    decorate(&mut inner, deco, &item, width, shift, Point::zero());
+                }
+                let group = GroupItem { transform, ..GroupItem::new(inner) };
+                frame.push(pos, FrameItem::Group(group));
             }
 
-            offset += width;
+            offset += width * scale;
         }
 
         frame
     }
 
+    /// The space added to the left and right of the glyph at the given index
+    /// when justifying.
+    fn justification(
+        &self,
+        index: usize,
+        justification_ratio: f64,
+        extra_justification: Abs,
+        extra_tracking: Abs,
+        ends_line: bool,
+    ) -> (Em, Em) {
+        let glyph = &self.glyphs[index];
+        let (left, right) = if justification_ratio < 0.0 {
+            glyph.shrinkability()
+        } else {
+            glyph.stretchability()
+        };
+
+        let left = left * justification_ratio;
+        let mut right = right * justification_ratio;
+        if glyph.is_justifiable() {
+            right += Em::from_length(extra_justification, self.size)
+                * glyph.justification_weight();
+        }
+        // There is no gap to fill after the last cluster of a line.
+        let last = ends_line && index + 1 == self.glyphs.len();
+        if self.is_cluster_end(index) && !last {
+            right += Em::from_length(extra_tracking, self.size);
+        }
+
+        (left, right)
+    }
+
+    /// Determine the horizontal extents of the glyphs that belong to the given
+    /// range of the paragraph's text, as offset and width in the frame that
+    /// [`build`](Self::build) creates with the same justification.
+    ///
+    /// Also returns the width of that frame.
+    #[allow(clippy::too_many_arguments)]
+    pub fn glyph_extents(
+        &self,
+        range: Range,
+        justification_ratio: f64,
+        extra_justification: Abs,
+        extra_tracking: Abs,
+        ends_line: bool,
+        kashidas: usize,
+        scale: f64,
+    ) -> (Vec<(Abs, Abs)>, Abs) {
+        let points = if kashidas > 0 { self.kashida_points() } else { vec![] };
+        let mut extents = vec![];
+        let mut offset = Abs::zero();
+        for (i, glyph) in self.glyphs.iter().enumerate() {
+            let (left, right) = self.justification(
+                i,
+                justification_ratio,
+                extra_justification,
+                extra_tracking,
+                ends_line,
+            );
+            let kashida = points
+                .iter()
+                .find(|&&(k, ..)| k == i)
+                .map_or(Em::zero(), |&(.., advance)| advance * kashidas as f64);
+            let advance =
+                ((glyph.x_advance + kashida) * scale + left + right).at(self.size);
+            if glyph.range.start < range.end && range.start < glyph.range.end {
+                extents.push((offset, advance));
+            }
+            offset += advance;
+        }
+        (extents, offset)
+    }
+
+    /// The points at which the text can be elongated with kashidas for
+    /// justification.
+    ///
+    /// Each point is given as the index of the glyph after which kashidas are
+    /// inserted, together with the font's kashida glyph and its advance. As the
+    /// glyphs of right-to-left text are in visual order, a point connects a
+    /// letter with the one logically before it, which must join to it.
+    pub fn kashida_points(&self) -> Vec<(usize, u16, Em)> {
+        if self.dir.is_positive() {
+            return vec![];
+        }
+
+        self.glyphs
+            .windows(2)
+            .enumerate()
+            .filter_map(|(i, pair)| {
+                let (next, prev) = (&pair[0], &pair[1]);
+                if prev.range.start == next.range.start
+                    || prev.font != next.font
+                    || !is_arabic_letter(next.c)
+                    || !joins_forward(prev.c)
+                {
+                    return None;
+                }
+
+                let id = prev.font.ttf().glyph_index('\u{640}')?.0;
+                Some((i, id, prev.font.advance(id)?))
+            })
+            .collect()
+    }
+
+    /// Determine how much to embolden and slant the glyphs of a font to
+    /// synthesize a requested weight or style that the font lacks.
+    ///
+    /// Returns the stroke thickness for emboldening and the horizontal shear
+    /// for obliquing, both zero if no synthesis is needed.
+    fn synthesis(&self, font: &Font) -> (Em, Ratio) {
+        if !TextElem::synthesis_in(self.styles).is_auto() {
+            return (Em::zero(), Ratio::zero());
+        }
+
+        let actual = font.info().variant;
+        let deficit = self
+            .variant
+            .weight
+            .to_number()
+            .saturating_sub(actual.weight.to_number());
+        let embolden = if deficit >= 200 { SYNTHETIC_BOLD } else { Em::zero() };
+
+        let slant = if self.variant.style != FontStyle::Normal
+            && actual.style == FontStyle::Normal
+        {
+            SYNTHETIC_SLANT
+        } else {
+            Ratio::zero()
+        };
+
+        (embolden, slant)
+    }
+
     /// Measure the top and bottom extent of this text.
     pub fn measure(&self, engine: &Engine) -> (Abs, Abs) {
         let mut top = Abs::zero();
@@ -377,6 +588,47 @@ impl<'a> ShapedText<'a> {
         self.glyphs.iter().filter(|g| g.is_justifiable()).count()
     }
 
+    /// The total justification weight of the justifiable glyphs.
+    pub fn justification_weight(&self) -> f64 {
+        self.glyphs
+            .iter()
+            .filter(|g| g.is_justifiable())
+            .map(ShapedGlyph::justification_weight)
+            .sum()
+    }
+
+    /// The natural width of the text's justifiable word spaces.
+    pub fn space_width(&self) -> Abs {
+        self.glyphs
+            .iter()
+            .filter(|g| g.is_justifiable() && g.is_justifiable_space())
+            .map(|g| g.x_advance)
+            .sum::<Em>()
+            .at(self.size)
+    }
+
+    /// The share of the text's justification weight that falls on its word
+    /// spaces.
+    pub fn space_weight(&self) -> f64 {
+        self.glyphs
+            .iter()
+            .filter(|g| g.is_justifiable() && g.is_justifiable_space())
+            .map(ShapedGlyph::justification_weight)
+            .sum()
+    }
+
+    /// The number of glyph clusters in the text.
+    pub fn clusters(&self) -> usize {
+        (0..self.glyphs.len()).filter(|&i| self.is_cluster_end(i)).count()
+    }
+
+    /// Whether the glyph at the given index is the last one of its cluster.
+    fn is_cluster_end(&self, index: usize) -> bool {
+        self.glyphs
+            .get(index + 1)
+            .map_or(true, |next| next.range != self.glyphs[index].range)
+    }
+
     /// Whether the last glyph is a CJK character which should not be justified
     /// on line end.
     pub fn cjk_justifiable_at_last(&self) -> bool {
@@ -407,8 +659,14 @@ impl<'a> ShapedText<'a> {
     /// Reshape a range of the shaped text, reusing information from this
     /// shaping process if possible.
     ///
-    /// The text `range` is relative to the whole paragraph.
-    pub fn reshape(&'a self, engine: &Engine, text_range: Range) -> ShapedText<'a> {
+    /// The text `range` is relative to the whole paragraph, whose `full` text
+    /// this run is part of.
+    pub fn reshape(
+        &'a self,
+        engine: &Engine,
+        full: &'a str,
+        text_range: Range,
+    ) -> ShapedText<'a> {
         let text = &self.text[text_range.start - self.base..text_range.end - self.base];
         if let Some(glyphs) = self.slice_safe_to_break(text_range.clone()) {
             #[cfg(debug_assertions)]
@@ -426,18 +684,33 @@ impl<'a> ShapedText<'a> {
                 glyphs: Cow::Borrowed(glyphs),
             }
         } else {
-            shape(
-                engine,
-                text_range.start,
-                text,
-                self.styles,
-                self.dir,
-                self.lang,
-                self.region,
-            )
+            shape(engine, full, text_range, self.styles, self.dir, self.lang, self.region)
         }
     }
 
+    /// Shape the text of this run anew with different styles.
+    ///
+    /// The `full` text is that of the whole paragraph this run is part of.
+    pub fn restyle<'b>(
+        &self,
+        engine: &Engine,
+        full: &'b str,
+        styles: StyleChain<'b>,
+    ) -> ShapedText<'b>
+    where
+        'a: 'b,
+    {
+        shape(
+            engine,
+            full,
+            self.base..self.base + self.text.len(),
+            styles,
+            self.dir,
+            TextElem::lang_in(styles),
+            TextElem::region_in(styles),
+        )
+    }
+
     /// Derive an empty text run with the same properties as this one.
     pub fn empty(&self) -> Self {
         Self {
@@ -449,8 +722,14 @@ impl<'a> ShapedText<'a> {
     }
 
     /// Push a hyphen to end of the text.
+    ///
+    /// This is the paragraph's configured hyphen character, if any.
     pub fn push_hyphen(&mut self, engine: &Engine, fallback: bool) {
-        self.insert_hyphen(engine, fallback, Side::Right)
+        match ParElem::hyphen_in(self.styles) {
+            Smart::Auto => self.insert_hyphen(engine, fallback, Side::Right),
+            Smart::Custom(Some(c)) => self.insert_char(engine, fallback, c, Side::Right),
+            Smart::Custom(None) => {}
+        }
     }
 
     /// Prepend a hyphen to start of the text.
@@ -458,11 +737,27 @@ impl<'a> ShapedText<'a> {
         self.insert_hyphen(engine, fallback, Side::Left)
     }
 
+    /// Push an ellipsis to the end of the text.
+    pub fn push_ellipsis(&mut self, engine: &Engine, fallback: bool) {
+        self.insert_char(engine, fallback, '…', Side::Right)
+    }
+
+    /// Prepend an ellipsis to the start of the text.
+    pub fn prepend_ellipsis(&mut self, engine: &Engine, fallback: bool) {
+        self.insert_char(engine, fallback, '…', Side::Left)
+    }
+
     fn insert_hyphen(&mut self, engine: &Engine, fallback: bool, side: Side) {
+        self.insert_char(engine, fallback, '-', side)
+    }
+
+    fn insert_char(&mut self, engine: &Engine, fallback: bool, c: char, side: Side) {
         let world = engine.world;
         let book = world.book();
+        let mut buf = [0; 4];
+        let text: &str = c.encode_utf8(&mut buf);
         let fallback_func = if fallback {
-            Some(|| book.select_fallback(None, self.variant, "-"))
+            Some(|| book.select_fallback(None, self.variant, text))
         } else {
             None
         };
@@ -474,7 +769,7 @@ impl<'a> ShapedText<'a> {
         chain.find_map(|id| {
             let font = world.font(id)?;
             let ttf = font.ttf();
-            let glyph_id = ttf.glyph_index('-')?;
+            let glyph_id = ttf.glyph_index(c)?;
             let x_advance = font.to_em(ttf.glyph_hor_advance(glyph_id)?);
             let range = match side {
                 Side::Left => self.glyphs.first().map(|g| g.range.start..g.range.start),
@@ -495,7 +790,7 @@ impl<'a> ShapedText<'a> {
                 adjustability: Adjustability::default(),
                 range,
                 safe_to_break: true,
-                c: '-',
+                c,
                 is_justifiable: false,
                 script: Script::Common,
             };
@@ -606,8 +901,7 @@ pub fn shape_range<'a>(
     let region = TextElem::region_in(styles);
     let mut process = |range: Range, level: BidiLevel| {
         let dir = if level.is_ltr() { Dir::LTR } else { Dir::RTL };
-        let shaped =
-            shape(engine, range.start, &text[range.clone()], styles, dir, lang, region);
+        let shaped = shape(engine, text, range.clone(), styles, dir, lang, region);
         items.push((range, Item::Text(shaped)));
     };
 
@@ -656,17 +950,22 @@ fn is_compatible(a: Script, b: Script) -> bool {
     is_generic_script(a) || is_generic_script(b) || a == b
 }
 
-/// Shape text into [`ShapedText`].
+/// Shape a range of the paragraph's full text into [`ShapedText`].
+///
+/// The text around the range provides context, for instance to determine
+/// whether a space separates two sentences.
 #[allow(clippy::too_many_arguments)]
-fn shape<'a>(
+pub fn shape<'a>(
     engine: &Engine,
-    base: usize,
-    text: &'a str,
+    full: &'a str,
+    range: Range,
     styles: StyleChain<'a>,
     dir: Dir,
     lang: Lang,
     region: Option<Region>,
 ) -> ShapedText<'a> {
+    let base = range.start;
+    let text = &full[range];
     let size = TextElem::size_in(styles);
     let mut ctx = ShapingContext {
         engine,
@@ -684,7 +983,7 @@ fn shape<'a>(
         shape_segment(&mut ctx, base, text, families(styles));
     }
 
-    track_and_space(&mut ctx);
+    track_and_space(&mut ctx, full, lang);
     calculate_adjustability(&mut ctx, lang, region);
 
     #[cfg(debug_assertions)]
@@ -759,36 +1058,19 @@ fn shape_segment<'a>(
 
     ctx.used.push(font.clone());
 
-    // Fill the buffer with our text.
-    let mut buffer = UnicodeBuffer::new();
-    buffer.push_str(text);
-    buffer.set_language(language(ctx.styles));
-    if let Some(script) = TextElem::script_in(ctx.styles).custom().and_then(|script| {
+    // Shape! The result only depends on the font, the text and the shaping
+    // properties, so it is reused across relayouts of an unchanged run.
+    let script = TextElem::script_in(ctx.styles).custom().and_then(|script| {
         rustybuzz::Script::from_iso15924_tag(Tag::from_bytes(script.as_bytes()))
-    }) {
-        buffer.set_script(script)
-    }
-    buffer.set_direction(match ctx.dir {
+    });
+    let direction = match ctx.dir {
         Dir::LTR => rustybuzz::Direction::LeftToRight,
         Dir::RTL => rustybuzz::Direction::RightToLeft,
         _ => unimplemented!("vertical text layout"),
-    });
-    buffer.guess_segment_properties();
-
-    // Prepare the shape plan. This plan depends on direction, script, language,
-    // and features, but is independent from the text and can thus be memoized.
-    let plan = create_shape_plan(
-        &font,
-        buffer.direction(),
-        buffer.script(),
-        buffer.language().as_ref(),
-        &ctx.features,
-    );
-
-    // Shape!
-    let buffer = rustybuzz::shape_with_plan(font.rusty(), &plan, buffer);
-    let infos = buffer.glyph_infos();
-    let pos = buffer.glyph_positions();
+    };
+    let run =
+        shape_run(&font, text, direction, script, &language(ctx.styles), &ctx.features);
+    let infos = &run.glyphs;
     let ltr = ctx.dir.is_positive();
 
     // Collect the shaped glyphs, doing fallback and shaping parts again with
@@ -809,17 +1091,17 @@ fn shape_segment<'a>(
 
             let c = text[cluster..].chars().next().unwrap();
             let script = c.script();
-            let x_advance = font.to_em(pos[i].x_advance);
+            let x_advance = font.to_em(info.x_advance);
             ctx.glyphs.push(ShapedGlyph {
                 font: font.clone(),
                 glyph_id: info.glyph_id as u16,
                 // TODO: Don't ignore y_advance.
                 x_advance,
-                x_offset: font.to_em(pos[i].x_offset),
-                y_offset: font.to_em(pos[i].y_offset),
+                x_offset: font.to_em(info.x_offset),
+                y_offset: font.to_em(info.y_offset),
                 adjustability: Adjustability::default(),
                 range: start..end,
-                safe_to_break: !info.unsafe_to_break(),
+                safe_to_break: info.safe_to_break,
                 c,
                 is_justifiable: is_justifiable(
                     c,
@@ -876,6 +1158,73 @@ fn shape_segment<'a>(
     ctx.used.pop();
 }
 
+/// The raw output of shaping a single run with one font.
+#[derive(Debug, Clone)]
+struct ShapedRun {
+    /// The shaped glyphs in visual order.
+    glyphs: Vec<RunGlyph>,
+}
+
+/// A glyph in a [`ShapedRun`], with its position in font units.
+#[derive(Debug, Clone)]
+struct RunGlyph {
+    glyph_id: u32,
+    cluster: u32,
+    safe_to_break: bool,
+    x_advance: i32,
+    x_offset: i32,
+    y_offset: i32,
+}
+
+/// Shape a run of text with a single font.
+#[comemo::memoize]
+fn shape_run(
+    font: &Font,
+    text: &str,
+    direction: rustybuzz::Direction,
+    script: Option<rustybuzz::Script>,
+    language: &rustybuzz::Language,
+    features: &[rustybuzz::Feature],
+) -> Arc<ShapedRun> {
+    // Fill the buffer with our text.
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.set_language(language.clone());
+    if let Some(script) = script {
+        buffer.set_script(script)
+    }
+    buffer.set_direction(direction);
+    buffer.guess_segment_properties();
+
+    // Prepare the shape plan. This plan depends on direction, script, language,
+    // and features, but is independent from the text and can thus be memoized.
+    let plan = create_shape_plan(
+        font,
+        buffer.direction(),
+        buffer.script(),
+        buffer.language().as_ref(),
+        features,
+    );
+
+    // Shape!
+    let buffer = rustybuzz::shape_with_plan(font.rusty(), &plan, buffer);
+    let glyphs = buffer
+        .glyph_infos()
+        .iter()
+        .zip(buffer.glyph_positions())
+        .map(|(info, pos)| RunGlyph {
+            glyph_id: info.glyph_id,
+            cluster: info.cluster,
+            safe_to_break: !info.unsafe_to_break(),
+            x_advance: pos.x_advance,
+            x_offset: pos.x_offset,
+            y_offset: pos.y_offset,
+        })
+        .collect();
+
+    Arc::new(ShapedRun { glyphs })
+}
+
 /// Create a shape plan.
 #[comemo::memoize]
 fn create_shape_plan(
@@ -928,10 +1277,12 @@ fn shape_tofus(ctx: &mut ShapingContext, base: usize, text: &str, font: Font) {
 }
 
 /// Apply tracking and spacing to the shaped glyphs.
-fn track_and_space(ctx: &mut ShapingContext) {
+fn track_and_space(ctx: &mut ShapingContext, full: &str, lang: Lang) {
     let tracking = Em::from_length(TextElem::tracking_in(ctx.styles), ctx.size);
     let spacing =
         TextElem::spacing_in(ctx.styles).map(|abs| Em::from_length(abs, ctx.size));
+    let sentence_spacing = ParElem::sentence_spacing_in(ctx.styles)
+        .map(|abs| Em::from_length(abs, ctx.size));
 
     let mut glyphs = ctx.glyphs.iter_mut().peekable();
     while let Some(glyph) = glyphs.next() {
@@ -942,6 +1293,11 @@ fn track_and_space(ctx: &mut ShapingContext) {
 
         if glyph.is_space() {
             glyph.x_advance = spacing.relative_to(glyph.x_advance);
+            if sentence_spacing != Rel::one()
+                && is_sentence_space(full, glyph.range.start, lang)
+            {
+                glyph.x_advance = sentence_spacing.relative_to(glyph.x_advance);
+            }
         }
 
         if glyphs
@@ -957,9 +1313,10 @@ fn track_and_space(ctx: &mut ShapingContext) {
 /// and CJK punctuation adjustments according to Chinese Layout Requirements.
 fn calculate_adjustability(ctx: &mut ShapingContext, lang: Lang, region: Option<Region>) {
     let style = cjk_punct_style(lang, region);
+    let space_shrink = ParElem::justify_shrink_in(ctx.styles);
 
     for glyph in &mut ctx.glyphs {
-        glyph.adjustability = glyph.base_adjustability(style);
+        glyph.adjustability = glyph.base_adjustability(style, space_shrink);
     }
 
     let mut glyphs = ctx.glyphs.iter_mut().peekable();
@@ -1075,6 +1432,72 @@ fn is_space(c: char) -> bool {
     matches!(c, ' ' | '\u{00A0}' | '　')
 }
 
+/// Whether the space at the given offset in the paragraph's text separates
+/// two sentences.
+///
+/// This is the case if it follows sentence-ending punctuation and precedes a
+/// capitalized word, unless the punctuation is the period of a common
+/// abbreviation in the given language, like "Dr." in English.
+fn is_sentence_space(full: &str, offset: usize, lang: Lang) -> bool {
+    let is_closing = |c: &char| matches!(c, '"' | '\'' | ')' | ']' | '”' | '’' | '»');
+    let is_opening = |c: &char| matches!(c, '"' | '\'' | '(' | '[' | '“' | '‘' | '«');
+
+    let mut before = full[..offset].chars().rev().skip_while(is_closing);
+    let Some(punct @ ('.' | '!' | '?')) = before.next() else { return false };
+
+    let mut after = full[offset..].chars().skip(1).skip_while(is_opening);
+    if !after.next().is_some_and(char::is_uppercase) {
+        return false;
+    }
+
+    if punct == '.' {
+        let mut word: Vec<char> =
+            before.take_while(|&c| c.is_alphabetic() || c == '.').collect();
+        word.reverse();
+        let word: String = word.into_iter().collect();
+        if abbreviations(lang).contains(&word.as_str()) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Common abbreviations that end with a period, without it.
+fn abbreviations(lang: Lang) -> &'static [&'static str] {
+    match lang {
+        Lang::ENGLISH => &[
+            "Mr", "Mrs", "Ms", "Dr", "Prof", "Rev", "St", "Jr", "Sr", "vs", "etc", "e.g",
+            "i.e", "cf", "approx", "Fig", "Eq", "Sec", "Ch", "Vol", "pp",
+        ],
+        Lang::GERMAN => &[
+            "Dr", "Prof", "Hr", "Fr", "St", "bzw", "ca", "vgl", "z.B", "u.a", "d.h",
+            "usw", "etc", "Nr", "Abb", "Tab", "Kap", "Bd",
+        ],
+        Lang::FRENCH => &[
+            "M", "MM", "Mme", "Mlle", "Dr", "Pr", "St", "Ste", "etc", "cf", "p", "fig",
+            "chap", "vol",
+        ],
+        Lang::SPANISH => &[
+            "Sr", "Sra", "Srta", "Dr", "Dra", "Prof", "etc", "p.ej", "pág", "fig", "cap",
+            "vol",
+        ],
+        _ => &[],
+    }
+}
+
+/// Whether the character is a typographic space with a fixed width (em, en,
+/// thin, or hair space). These neither break nor stretch.
+pub fn is_fixed_space(c: char) -> bool {
+    matches!(c, '\u{2002}' | '\u{2003}' | '\u{2009}' | '\u{200A}')
+}
+
+/// Whether the character is a space that can be stretched or shrunk for
+/// justification. Non-breaking spaces keep their natural width.
+fn is_justifiable_space(c: char) -> bool {
+    is_space(c) && c != '\u{00A0}'
+}
+
 /// Whether the glyph is part of Chinese or Japanese script (i.e. CJ, not CJK).
 pub fn is_of_cj_script(c: char) -> bool {
     is_cj_script(c, c.script())
@@ -1089,6 +1512,35 @@ fn is_cj_script(c: char, script: Script) -> bool {
     matches!(script, Hiragana | Katakana | Han) || c == '\u{30FC}'
 }
 
+/// Whether the character is an Arabic letter, which may be connected to its
+/// neighbours with kashidas.
+fn is_arabic_letter(c: char) -> bool {
+    c.script() == Script::Arabic && c.is_alphabetic() && c != '\u{640}'
+}
+
+/// Whether the character is an Arabic letter that joins to the letter
+/// following it, i.e. one that is neither right-joining nor non-joining.
+fn joins_forward(c: char) -> bool {
+    is_arabic_letter(c)
+        && !matches!(
+            c,
+            '\u{621}'..='\u{625}'
+                | '\u{627}'
+                | '\u{629}'
+                | '\u{62F}'..='\u{632}'
+                | '\u{648}'
+                | '\u{671}'..='\u{677}'
+                | '\u{688}'..='\u{699}'
+                | '\u{6C0}'
+                | '\u{6C3}'..='\u{6CB}'
+                | '\u{6CD}'
+                | '\u{6CF}'
+                | '\u{6D2}'..='\u{6D5}'
+                | '\u{6EE}'
+                | '\u{6EF}'
+        )
+}
+
 /// See <https://www.w3.org/TR/clreq/#punctuation_width_adjustment>
 fn is_cjk_left_aligned_punctuation(
     c: char,
@@ -1163,7 +1615,7 @@ fn is_justifiable(
 ) -> bool {
     // punctuation style is not relevant here.
     let style = CjkPunctStyle::Gb;
-    is_space(c)
+    is_justifiable_space(c)
         || is_cj_script(c, script)
         || is_cjk_left_aligned_punctuation(c, x_advance, stretchability, style)
         || is_cjk_right_aligned_punctuation(c, x_advance, stretchability)
@@ -68,6 +68,7 @@ pub use self::stack::*;
 pub use self::transform::*;
 
 pub(crate) use self::inline::*;
+pub use self::inline::{BidiRun, GlyphExtent, Hyphenation, LineInfo, LineMetrics};
 
 use comemo::{Track, Tracked, TrackedMut};
 
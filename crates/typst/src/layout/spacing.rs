@@ -16,6 +16,18 @@ use crate::utils::Numeric;
 /// First #h(2fr) Second #h(1fr) Third
 /// ```
 ///
+/// # Negative Spacing
+/// Absolute and relative spacing may also be negative to pull the content
+/// around it closer together, for instance to manually kern two letters or to
+/// tuck a box into the preceding text. The content may then overlap, which is
+/// intended. Line breaking and justification account for the reduced width of
+/// the line.
+///
+/// ```example
+/// T#h(-0.1em)o \
+/// A#h(-0.3em)V
+/// ```
+///
 /// # Mathematical Spacing { #math-spacing }
 /// In [mathematical formulas]($category/math), you can additionally use these
 /// constants to add spacing between elements: `thin`, `med`, `thick`, `quad`, `wide`.
@@ -52,6 +64,22 @@ pub struct HElem {
     /// ```
     #[default(false)]
     pub weak: bool,
+
+    /// How much the spacing may grow beyond its amount in a justified line.
+    ///
+    /// Stretchable spacing absorbs the space that is left over when a line is
+    /// justified before the line's word spaces do, in proportion to how much
+    /// each such spacing may stretch. It never shrinks below its amount. This
+    /// gives control over where the slack of a justified line ends up. The
+    /// stretch has no effect on fractional spacing.
+    ///
+    /// ```example
+    /// #set par(justify: true)
+    /// Name: #h(0.5em, stretch: 5cm) Jane Doe \
+    /// Occupation: #h(0.5em, stretch: 5cm) Typesetter
+    /// ```
+    #[resolve]
+    pub stretch: Length,
 }
 
 impl HElem {
@@ -1,14 +1,24 @@
 use std::fmt::{self, Debug, Formatter};
+use std::num::NonZeroUsize;
+use std::str::FromStr;
 
-use crate::diag::SourceResult;
+use ecow::EcoString;
+
+use crate::diag::{bail, HintedStrResult, SourceResult};
 use crate::engine::Engine;
 use crate::foundations::{
-    elem, Args, Cast, Construct, Content, NativeElement, Packed, Set, Smart, StyleChain,
-    Unlabellable,
+    cast, elem, Args, Cast, Construct, Content, Dict, IntoValue, NativeElement, Packed,
+    Set, Smart, StyleChain, Unlabellable,
 };
 use crate::introspection::Locator;
-use crate::layout::{Em, Fragment, Length, Size};
+use crate::layout::{
+    Abs, BidiRun, Em, Fragment, GlyphExtent, HAlignment, Hyphenation, Length, LineFrames,
+    LineInfo, LineMetrics, OuterHAlignment, Ratio, Rel, Size,
+};
+use crate::model::Numbering;
 use crate::realize::StyleVec;
+use crate::syntax::Spanned;
+use crate::text::Lang;
 
 /// Arranges text, spacing and inline-level elements into a paragraph.
 ///
@@ -55,6 +65,88 @@ pub struct ParElem {
     #[default(Em::new(0.65).into())]
     pub leading: Length,
 
+    /// The spacing between lines relative to the height of the line above.
+    ///
+    /// When set, this takes precedence over the fixed
+    /// [`leading`]($par.leading) between the lines of a paragraph. The spacing
+    /// then grows with lines that are taller because of larger text or inline
+    /// content. When `{none}`, the fixed leading is used.
+    ///
+    /// ```example
+    /// #set par(leading-ratio: 50%)
+    /// Short lines are close, \
+    /// but #text(2em)[large] ones \
+    /// are further apart.
+    /// ```
+    #[ghost]
+    #[default(None)]
+    pub leading_ratio: Option<Ratio>,
+
+    /// Whether the spacing between lines adapts to the size of the text in
+    /// the line below.
+    ///
+    /// When enabled, the [`leading`]($par.leading) below each line is scaled
+    /// by the ratio between the largest text size in the next line and the
+    /// paragraph's text size. This keeps the spacing visually even in
+    /// paragraphs that mix text sizes. When disabled, all lines are spaced
+    /// the same, which keeps a fixed rhythm regardless of the content.
+    ///
+    /// ```example
+    /// #set par(leading-adaptive: true)
+    /// Small lines are close, \
+    /// #text(1.5em)[large ones] \
+    /// are further apart.
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub leading_adaptive: bool,
+
+    /// A baseline grid that the baselines of all lines snap to.
+    ///
+    /// When set, each line is moved down such that its baseline lands on the
+    /// next multiple of the given step, measured from the top of the current
+    /// region. The [`leading`]($par.leading) then only acts as a minimum
+    /// distance between lines. Lines that are taller than a single step
+    /// simply occupy multiple steps.
+    ///
+    /// This keeps text in neighbouring columns aligned, even when some lines
+    /// contain taller content.
+    ///
+    /// ```example
+    /// #set page(height: 100pt, columns: 2)
+    /// #set par(baseline-grid: 12pt)
+    /// #lorem(5)
+    ///
+    /// $ sum_(i=0)^n i $
+    ///
+    /// #lorem(12)
+    /// ```
+    #[resolve]
+    #[ghost]
+    pub baseline_grid: Option<Length>,
+
+    /// The distance from the top of the paragraph to the baseline of its
+    /// first line.
+    ///
+    /// When set to `{auto}`, the first line's baseline sits as far below the
+    /// top as the line extends above it, which is determined by the text's
+    /// [top edge]($text.top-edge). Setting a length instead places the
+    /// baseline precisely, for example to align it to a grid or to adjacent
+    /// content.
+    ///
+    /// ```example
+    /// #set par(first-baseline: 12pt)
+    /// #grid(
+    ///   columns: 2,
+    ///   gutter: 6pt,
+    ///   text(8pt)[Small text],
+    ///   text(14pt)[Large text],
+    /// )
+    /// ```
+    #[resolve]
+    #[ghost]
+    pub first_baseline: Smart<Length>,
+
     /// The spacing between paragraphs.
     ///
     /// Just like leading, this defines the spacing between the bottom edge of a
@@ -70,6 +162,25 @@ pub struct ParElem {
     #[default(Em::new(1.2).into())]
     pub spacing: Length,
 
+    /// The width in which the paragraph's lines are set.
+    ///
+    /// When set to `{auto}`, the lines fill the available width. Otherwise,
+    /// they are set in the given width, which is relative to the available
+    /// width. The narrower lines are then placed according to the horizontal
+    /// [alignment]($align.alignment) of the paragraph.
+    ///
+    /// ```example
+    /// #lorem(10)
+    ///
+    /// #par(width: 80%, justify: true)[
+    ///   #set align(center)
+    ///   #lorem(16)
+    /// ]
+    /// ```
+    #[ghost]
+    #[resolve]
+    pub width: Smart<Rel<Length>>,
+
     /// Whether to justify text in its line.
     ///
     /// Hyphenation will be enabled for justified paragraphs if the
@@ -78,11 +189,197 @@ pub struct ParElem {
     ///
     /// Note that the current [alignment]($align.alignment) still has an effect
     /// on the placement of the last line except if it ends with a
-    /// [justified line break]($linebreak.justify).
+    /// [justified line break]($linebreak.justify) or
+    /// [`justify-last`]($par.justify-last) is enabled.
     #[ghost]
     #[default(false)]
     pub justify: bool,
 
+    /// Whether to also justify the last line of a justified paragraph.
+    ///
+    /// By default, the last line keeps its natural width and is placed
+    /// according to the current [alignment]($align.alignment). Lines ending in
+    /// a forced [line break]($linebreak) are not affected by this property;
+    /// use the line break's [`justify`]($linebreak.justify) parameter for
+    /// those.
+    ///
+    /// ```example
+    /// #set page(width: 160pt)
+    /// #set par(justify: true, justify-last: true)
+    /// This paragraph is fully justified,
+    /// down to its very last line.
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub justify_last: bool,
+
+    /// How to align the last line of the paragraph.
+    ///
+    /// When set to `{auto}`, the last line is aligned like all other lines
+    /// according to the current [alignment]($align.alignment). This is mostly
+    /// useful for justified paragraphs, where it allows, for instance, to
+    /// center the last line while all others fill the whole width. It has no
+    /// effect if the last line is justified.
+    ///
+    /// ```example
+    /// #set page(width: 160pt)
+    /// #set par(justify: true, last-line-align: center)
+    /// This paragraph is justified, except for its last line,
+    /// which is centered.
+    /// ```
+    #[ghost]
+    #[resolve]
+    pub last_line_align: Smart<HAlignment>,
+
+    /// How much word spaces may shrink in a justified paragraph, relative to
+    /// their natural width.
+    ///
+    /// Allowing spaces to shrink lets a line that is marginally too long stay
+    /// on one line instead of forcing a break, which reduces hyphenation and
+    /// overfull lines in narrow columns. A value of `{0%}` keeps spaces at
+    /// least at their natural width.
+    ///
+    /// ```example
+    /// #set page(width: 190pt)
+    /// #set par(justify: true)
+    /// #set text(hyphenate: false)
+    /// #lorem(12)
+    ///
+    /// #set par(justify-shrink: 0%)
+    /// #lorem(12)
+    /// ```
+    #[ghost]
+    #[default(Ratio::new(1.0 / 3.0))]
+    pub justify_shrink: Ratio,
+
+    /// How much word spaces may stretch at most in a justified paragraph,
+    /// relative to their natural width.
+    ///
+    /// When `{none}`, spaces are stretched as much as necessary to fill the
+    /// line, which can produce large gaps on sparse lines. With a limit, any
+    /// space that remains is instead left at the end of the line according to
+    /// the current [alignment]($align.alignment) or, if
+    /// [`justify-glyphs`]($par.justify-glyphs) is enabled, spread between the
+    /// line's characters. Only word spaces are limited: Other justifiable
+    /// characters, like in Chinese or Japanese text, still take up extra
+    /// space as usual.
+    ///
+    /// ```example
+    /// #set page(width: 150pt)
+    /// #set par(justify: true, justify-stretch: 100%)
+    /// A line with a long word:
+    /// Incomprehensibilities.
+    /// ```
+    #[ghost]
+    #[default(None)]
+    pub justify_stretch: Option<Ratio>,
+
+    /// Whether to spread space between all characters of a justified line if
+    /// it can't be filled otherwise.
+    ///
+    /// Normally, justification only adjusts word spaces and, in Chinese and
+    /// Japanese text, the spacing around characters. Lines without any of
+    /// those, like a single long word, then keep their natural width. When
+    /// this is enabled, the remaining space is instead distributed evenly
+    /// between the line's characters.
+    ///
+    /// ```example
+    /// #set page(width: 120pt)
+    /// #set par(justify: true, justify-last: true)
+    /// #upper[Typesetting]
+    ///
+    /// #set par(justify-glyphs: true)
+    /// #upper[Typesetting]
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub justify_glyphs: bool,
+
+    /// Whether to justify lines of Arabic text by elongating its words with
+    /// kashidas instead of only widening the spaces between them.
+    ///
+    /// A kashida (or tatweel) stretches the connection between two joined
+    /// letters. When this is enabled, the same number of kashidas is inserted
+    /// between all joined letters of a justified line, as many as fit. Any
+    /// space that remains is distributed between the words as usual.
+    ///
+    /// ```example
+    /// #set page(width: 160pt)
+    /// #set text(font: "Noto Sans Arabic", lang: "ar")
+    /// #set par(justify: true, justify-kashida: true)
+    /// هذا نص عربي بسيط يوضح كيفية ضبط الأسطر بالكشيدة.
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub justify_kashida: bool,
+
+    /// How much the glyphs of a line may be scaled horizontally at most to
+    /// fill or fit the line, relative to their natural width.
+    ///
+    /// When a justified line can't be filled by adjusting its word spaces
+    /// alone, its glyphs are uniformly expanded by up to this ratio. Likewise,
+    /// a line that is too wide even after shrinking its spaces is condensed.
+    /// For instance, `{2%}` permits widths between 98% and 102%. Small values
+    /// are barely visible, but can noticeably even out the spacing of
+    /// justified text. Zero disables glyph scaling. The ratio must be less
+    /// than `{100%}`.
+    ///
+    /// ```example
+    /// #set page(width: 120pt)
+    /// #set par(justify: true, justify-scale: 3%)
+    /// #lorem(16)
+    /// ```
+    #[parse({
+        let scale: Option<Spanned<Ratio>> = args.named("justify-scale")?;
+        if let Some(Spanned { v, span }) = scale {
+            if v < Ratio::zero() || v >= Ratio::one() {
+                bail!(span, "justify scale must be at least 0% and less than 100%");
+            }
+        }
+        scale.map(|scale| scale.v)
+    })]
+    #[ghost]
+    pub justify_scale: Ratio,
+
+    /// How much the leading between lines may stretch at most to fill a
+    /// region vertically, relative to the leading.
+    ///
+    /// When set, the lines of a paragraph are spread out if the region they
+    /// are in is broken before it is full, such that the text ends at the
+    /// bottom of the region. This is useful for columns that should be
+    /// filled completely. The last region of a flow and regions ended by an
+    /// explicit column or page break are never justified. When `{none}`, the
+    /// leading is never stretched.
+    ///
+    /// ```example
+    /// #set page(height: 100pt, columns: 2)
+    /// #set par(justify-vertical: 100%)
+    /// #lorem(30)
+    /// ```
+    #[ghost]
+    #[default(None)]
+    pub justify_vertical: Option<Ratio>,
+
+    /// The width of spaces between sentences, relative to the width of a
+    /// regular word space.
+    ///
+    /// A space counts as a sentence boundary if it follows a period,
+    /// exclamation mark, or question mark and precedes a capitalized word.
+    /// The period of a common abbreviation in the text's language, like "Dr."
+    /// in English, doesn't end a sentence. Wider
+    /// sentence spaces also stretch more when justifying and lines can still
+    /// be broken at them.
+    ///
+    /// ```example
+    /// #set par(sentence-spacing: 200%)
+    /// This is a sentence. Dr. Smith wrote
+    /// it! Did you know that?
+    /// ```
+    #[ghost]
+    #[resolve]
+    #[default(Rel::one())]
+    pub sentence_spacing: Rel<Length>,
+
     /// How to determine line breaks.
     ///
     /// When this property is set to `{auto}`, its default value, optimized line
@@ -107,6 +404,308 @@ pub struct ParElem {
     #[ghost]
     pub linebreaks: Smart<Linebreaks>,
 
+    /// Whether to break overlong words that don't fit into a line on their
+    /// own.
+    ///
+    /// By default, a word that is wider than the available space (e.g. a long
+    /// URL or chemical name) overflows its line. When this is enabled, such
+    /// words are instead broken at the character closest to the end of the
+    /// line. This only happens when there is no other way to break the line,
+    /// so it does not affect the rest of the paragraph.
+    ///
+    /// ```example
+    /// #set page(width: 120pt)
+    /// #set par(emergency-breaks: true)
+    /// Pneumonoultramicroscopicsilicovolcanoconiosis
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub emergency_breaks: bool,
+
+    /// Whether to keep spaces at the end of lines.
+    ///
+    /// By default, spaces at which a line is broken are removed, so that they
+    /// neither count towards the line's width nor show up at its end. When
+    /// this is enabled, such spaces are kept and take up room at the end of
+    /// the line. They are still not stretched or shrunk when the paragraph is
+    /// justified.
+    ///
+    /// ```example
+    /// #set par(keep-trailing-spaces: true)
+    /// #box(fill: aqua, width: 80pt)[Spaces at the end of a line]
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub keep_trailing_spaces: bool,
+
+    /// The badness up to which a justified line is considered acceptable.
+    ///
+    /// The badness of a line measures how far its spaces need to be stretched
+    /// and is computed the same way as in TeX: It is `{100}` when the spaces
+    /// stretch exactly as far as their stretchability permits and grows with
+    /// the cube of the stretch beyond that. Optimized line breaking avoids
+    /// lines whose badness exceeds the tolerance, for instance by hyphenating
+    /// a word, and a warning that includes the badness is emitted for every
+    /// such line that remains.
+    ///
+    /// When `{none}`, lines may be arbitrarily loose and no warnings are
+    /// emitted.
+    ///
+    /// ```example
+    /// #set page(width: 160pt)
+    /// #set par(justify: true, tolerance: 200)
+    /// This paragraph is set with a
+    /// tolerance similar to the one
+    /// LaTeX uses by default.
+    /// ```
+    #[parse({
+        let tolerance: Option<Spanned<Option<f64>>> = args.named("tolerance")?;
+        if let Some(Spanned { v: Some(v), span }) = tolerance {
+            if v.is_nan() || v < 0.0 {
+                bail!(span, "tolerance must be a non-negative number");
+            }
+        }
+        tolerance.map(|tolerance| tolerance.v)
+    })]
+    #[ghost]
+    #[default(None)]
+    pub tolerance: Option<f64>,
+
+    /// How far a line may extend beyond the available width before a warning
+    /// is emitted.
+    ///
+    /// A line becomes overfull when it cannot be broken in a way that fits the
+    /// available width, for example because of a long word. When this is set,
+    /// each line that overflows by more than the given length is reported
+    /// together with the amount by which it is too wide. When `{none}`, no
+    /// warnings are emitted.
+    ///
+    /// ```example
+    /// #set page(width: 120pt)
+    /// #set par(overfull-tolerance: 1pt)
+    /// Pneumonoultramicroscopicsilicovolcanoconiosis
+    /// ```
+    #[ghost]
+    #[resolve]
+    #[default(None)]
+    pub overfull_tolerance: Option<Length>,
+
+    /// The minimum number of characters that must remain before a hyphenation
+    /// break.
+    ///
+    /// When set to `{auto}`, the hyphenation patterns of the current language
+    /// decide, which typically results in a limit of two characters. Setting
+    /// a number only filters out additional breaks; it cannot allow breaks
+    /// that the patterns forbid.
+    ///
+    /// ```example
+    /// #set page(width: 60pt)
+    /// #set text(hyphenate: true)
+    /// #set par(hyphenate-before: 4)
+    /// This is an unbelievable sentence.
+    /// ```
+    #[ghost]
+    pub hyphenate_before: Smart<usize>,
+
+    /// The minimum number of characters that must remain after a hyphenation
+    /// break.
+    ///
+    /// Just like [`hyphenate-before`]($par.hyphenate-before), this defaults to
+    /// the limit of the current language's hyphenation patterns, which is
+    /// typically three characters.
+    #[ghost]
+    pub hyphenate_after: Smart<usize>,
+
+    /// The maximum number of consecutive lines that may end in a hyphenation.
+    ///
+    /// When the limit is reached, a line break without hyphenation is chosen
+    /// instead, even if this results in a looser line. When set to `{none}`,
+    /// the number of consecutive hyphenated lines is unlimited.
+    ///
+    /// With [optimized]($par.linebreaks) line breaking, exceeding the limit is
+    /// very costly instead of being forbidden outright. It is thus only
+    /// exceeded if there is no other way to break the paragraph.
+    ///
+    /// ```example
+    /// #set page(width: 80pt)
+    /// #set text(hyphenate: true)
+    /// #set par(hyphenate-lines: 1)
+    /// #lorem(16)
+    /// ```
+    #[ghost]
+    pub hyphenate_lines: Option<usize>,
+
+    /// How much of the available width a line of a ragged paragraph may leave
+    /// empty before a hyphenation is used to fill it.
+    ///
+    /// By default, [simple]($par.linebreaks) line breaking hyphenates a word
+    /// whenever part of it still fits into the line. With a tolerance, a word
+    /// is only hyphenated if the line would otherwise fall short of the
+    /// available width by more than the given ratio. Higher values thus lead
+    /// to fewer hyphens, but more uneven line lengths. This has no effect on
+    /// justified paragraphs and on optimized line breaking.
+    ///
+    /// ```example
+    /// #set page(width: 80pt)
+    /// #set text(hyphenate: true)
+    /// #set par(linebreaks: "simple", ragged-tolerance: 20%)
+    /// #lorem(16)
+    /// ```
+    #[ghost]
+    #[default(None)]
+    pub ragged_tolerance: Option<Ratio>,
+
+    /// Words with explicit hyphenation points, keyed by language.
+    ///
+    /// Each word is given with hyphens at the positions where it may be
+    /// broken. When hyphenating a word of the given language, these positions
+    /// are used instead of the language's hyphenation patterns. Words are
+    /// matched regardless of their case.
+    ///
+    /// ```example
+    /// #set page(width: 80pt)
+    /// #set text(hyphenate: true)
+    /// #set par(hyphenate-exceptions: (
+    ///   en: ("man-u-script", "Typst"),
+    /// ))
+    /// Typst sets this manuscript.
+    /// ```
+    #[ghost]
+    #[borrowed]
+    pub hyphenate_exceptions: HyphenationExceptions,
+
+    /// The character that is added at the end of a line when a word is
+    /// hyphenated.
+    ///
+    /// When set to `{auto}`, a regular hyphen is used. Some orthographies
+    /// and styles use a different character, such as a double hyphen. When
+    /// set to `{none}`, no character is added at all, which can be useful if
+    /// the text already contains explicit break points at existing hyphens.
+    /// Hyphens that are repeated at the start of the next line are not
+    /// affected.
+    ///
+    /// ```example
+    /// #set page(width: 80pt)
+    /// #set text(hyphenate: true, lang: "de")
+    /// #set par(hyphen: "=")
+    /// Die Silbentrennung beim Schriftsatz.
+    /// ```
+    #[ghost]
+    pub hyphen: Smart<Option<char>>,
+
+    /// Additional characters that a line must not start with.
+    ///
+    /// Lines are never broken before closing brackets, percent and per-mille
+    /// signs, even if a space precedes them. The characters given here are
+    /// kept with the preceding word in the same way.
+    ///
+    /// ```example
+    /// #set page(width: 80pt)
+    /// #set text(lang: "fr")
+    /// #set par(nobreak-before: (":", ";", "!", "?"))
+    /// Voici la question : pourquoi ?
+    /// ```
+    #[ghost]
+    #[borrowed]
+    pub nobreak_before: Vec<char>,
+
+    /// Additional characters that a line must not end with.
+    ///
+    /// Lines are never broken after opening brackets and some opening
+    /// punctuation like `¿` and `„`, even if a space follows them. The
+    /// characters given here are kept with the following word in the same
+    /// way.
+    ///
+    /// ```example
+    /// #set page(width: 80pt)
+    /// #set text(lang: "fr")
+    /// #set par(nobreak-after: ("«",))
+    /// Il a dit « bonjour » à tout le monde.
+    /// ```
+    #[ghost]
+    #[borrowed]
+    pub nobreak_after: Vec<char>,
+
+    /// Whether the last word of a paragraph may be hyphenated.
+    ///
+    /// A hyphen that splits the final word across the last two lines is
+    /// widely discouraged, so this is disabled by default.
+    ///
+    /// ```example
+    /// #set page(width: 80pt)
+    /// #set text(hyphenate: true)
+    /// #set par(hyphenate-last-word: true)
+    /// This is an unbelievable sentence.
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub hyphenate_last_word: bool,
+
+    /// Whether words starting with an uppercase letter may be hyphenated.
+    ///
+    /// Many house styles forbid hyphenating capitalized words, as these are
+    /// often proper nouns.
+    ///
+    /// ```example
+    /// #set page(width: 80pt)
+    /// #set text(hyphenate: true)
+    /// #set par(hyphenate-capitalized: false)
+    /// Constantinople was renamed to Istanbul.
+    /// ```
+    #[ghost]
+    #[default(true)]
+    pub hyphenate_capitalized: bool,
+
+    /// Whether lines may be broken within identifiers like `camelCase` or
+    /// `snake_case` names.
+    ///
+    /// When enabled, words that look like identifiers may be broken before an
+    /// uppercase letter that follows a lowercase one and after underscores.
+    /// No hyphen is inserted at these breaks, so that the identifier isn't
+    /// misrepresented. Only words that start with a lowercase letter or
+    /// contain underscores are considered, so that regular words and names
+    /// like "McDonald" are unaffected. This keeps technical prose with long
+    /// identifiers from overflowing.
+    ///
+    /// ```example
+    /// #set page(width: 100pt)
+    /// #set par(break-identifiers: true)
+    /// Call getElementsByClassName or
+    /// read the MAX_CONNECTION_COUNT.
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub break_identifiers: bool,
+
+    /// The maximum number of lines of the paragraph.
+    ///
+    /// When the paragraph has more lines, the remaining ones are dropped and
+    /// the last visible line is shortened to end with an ellipsis. When set
+    /// to `{none}`, all lines are shown.
+    ///
+    /// ```example
+    /// #set page(width: 120pt)
+    /// #set par(max-lines: 2)
+    /// #lorem(20)
+    /// ```
+    #[ghost]
+    pub max_lines: Option<NonZeroUsize>,
+
+    /// What to do with lines that are wider than the paragraph.
+    ///
+    /// A line can only become too wide if it contains something that cannot
+    /// be broken, like a long word or a wide box. By default, such a line
+    /// overflows the paragraph. This is useful in containers of a fixed size,
+    /// where the content must not escape its bounds.
+    ///
+    /// ```example
+    /// #set page(width: 120pt)
+    /// #set par(overflow: "truncate")
+    /// Pneumonoultramicroscopicsilicovolcanoconiosis
+    /// ```
+    #[ghost]
+    pub overflow: Overflow,
+
     /// The indent the first line of a paragraph should have.
     ///
     /// Only the first line of a consecutive paragraph will be indented (not
@@ -124,6 +723,131 @@ pub struct ParElem {
     #[resolve]
     pub hanging_indent: Length,
 
+    /// Text properties to apply to the first line of the paragraph only.
+    ///
+    /// The arguments are the same as those of the [`text`] function. Since the
+    /// extent of the first line is only known once the paragraph is broken
+    /// into lines, the line is broken as if it was set normally and then
+    /// reshaped with the given properties. Properties that make the text wider
+    /// can thus make the first line overflow.
+    ///
+    /// ```example
+    /// #set par(first-line: arguments(tracking: 0.1em, weight: "bold"))
+    /// #lorem(20)
+    /// ```
+    #[ghost]
+    pub first_line: Option<Args>,
+
+    /// The minimum number of lines of a paragraph that are kept together at
+    /// the bottom of a region when the paragraph breaks across regions.
+    ///
+    /// If fewer lines would fit, they are moved to the next region along with
+    /// the rest of the paragraph. Values below `{2}` and an
+    /// [orphan cost]($text.costs) of `{0%}` disable orphan prevention.
+    ///
+    /// ```example
+    /// #set page(height: 60pt)
+    /// #set par(orphans: 3)
+    /// #v(20pt)
+    /// #lorem(16)
+    /// ```
+    #[ghost]
+    #[default(2)]
+    pub orphans: usize,
+
+    /// The minimum number of lines of a paragraph that are kept together at
+    /// the top of a region when the paragraph breaks across regions.
+    ///
+    /// If fewer lines would be carried over, more lines are moved to the next
+    /// region. Values below `{2}` and a [widow cost]($text.costs) of `{0%}`
+    /// disable widow prevention.
+    #[ghost]
+    #[default(2)]
+    pub widows: usize,
+
+    /// Whether to keep a paragraph from breaking across regions.
+    ///
+    /// When set to `{true}`, the paragraph is always kept together. When set to
+    /// an integer, paragraphs with at most that many lines are kept together,
+    /// while longer ones may still break. A paragraph that is kept together
+    /// and doesn't fit into the rest of the current region is moved to the
+    /// next one as a whole. This is useful for short notes and captions. A
+    /// paragraph that wouldn't even fit into an empty region is still split.
+    ///
+    /// ```example
+    /// #set page(height: 80pt)
+    /// #set par(keep-together: 4)
+    /// #v(30pt)
+    /// #lorem(16)
+    /// ```
+    #[ghost]
+    pub keep_together: KeepTogether,
+
+    /// Whether to keep the paragraph in the same region as the first line of
+    /// the paragraph that follows it.
+    ///
+    /// If the following paragraph would start in the next region, this
+    /// paragraph is moved there along with it. This is useful for short
+    /// paragraphs that act like headings and shouldn't be stranded at the
+    /// bottom of a page.
+    ///
+    /// ```example
+    /// #set page(height: 80pt)
+    /// #v(40pt)
+    /// #par(keep-with-next: true)[*Summary*]
+    /// #lorem(8)
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub keep_with_next: bool,
+
+    /// How to number the paragraph's lines.
+    ///
+    /// When set, line numbers are displayed in the margin next to the lines.
+    /// The count continues across the paragraphs of a page or container and
+    /// only includes lines that are numbered.
+    ///
+    /// ```example
+    /// #set page(margin: (left: 40pt))
+    /// #set par(line-numbering: "1")
+    /// Roses are red, \
+    /// violets are blue.
+    ///
+    /// Sugar is sweet, \
+    /// and so are you.
+    /// ```
+    #[ghost]
+    #[borrowed]
+    pub line_numbering: Option<Numbering>,
+
+    /// Only every n-th line is labelled with its number.
+    ///
+    /// ```example
+    /// #set page(margin: (left: 40pt))
+    /// #set par(line-numbering: "1", line-numbering-every: 2)
+    /// #lorem(20)
+    /// ```
+    #[ghost]
+    #[default(NonZeroUsize::ONE)]
+    pub line_numbering_every: NonZeroUsize,
+
+    /// On which side of the text the line numbers are displayed.
+    ///
+    /// ```example
+    /// #set page(margin: (right: 40pt))
+    /// #set par(line-numbering: "1", line-numbering-side: end)
+    /// #lorem(12)
+    /// ```
+    #[ghost]
+    #[resolve]
+    pub line_numbering_side: OuterHAlignment,
+
+    /// The gap between the line numbers and the text.
+    #[ghost]
+    #[resolve]
+    #[default(Em::new(1.0).into())]
+    pub line_numbering_gap: Length,
+
     /// Indicates wheter an overflowing line should be shrunk.
     ///
     /// This property is set to `false` on raw blocks, because shrinking a line
@@ -161,7 +885,6 @@ impl Construct for ParElem {
 
 impl Packed<ParElem> {
     /// Layout the paragraph into a collection of lines.
-    #[typst_macros::time(name = "par", span = self.span())]
     pub fn layout(
         &self,
         engine: &mut Engine,
@@ -171,6 +894,24 @@ impl Packed<ParElem> {
         region: Size,
         expand: bool,
     ) -> SourceResult<Fragment> {
+        self.layout_lines(engine, locator, styles, consecutive, region, expand)
+            .map(LineFrames::merge)
+    }
+
+    /// Layout the paragraph into one frame per line.
+    ///
+    /// Also determines the leading that belongs between each pair of
+    /// consecutive lines and which lines must be kept in the same region.
+    #[typst_macros::time(name = "par", span = self.span())]
+    pub(crate) fn layout_lines(
+        &self,
+        engine: &mut Engine,
+        locator: Locator,
+        styles: StyleChain,
+        consecutive: bool,
+        region: Size,
+        expand: bool,
+    ) -> SourceResult<LineFrames> {
         crate::layout::layout_inline(
             &self.children,
             engine,
@@ -181,6 +922,165 @@ impl Packed<ParElem> {
             expand,
         )
     }
+
+    /// Determine where the paragraph's lines are broken.
+    ///
+    /// Since paragraph layout is memoized, calling this with the same
+    /// arguments as [`layout`](Self::layout) does not lay out the paragraph
+    /// again.
+    pub fn lines(
+        &self,
+        engine: &mut Engine,
+        locator: Locator,
+        styles: StyleChain,
+        consecutive: bool,
+        region: Size,
+        expand: bool,
+    ) -> SourceResult<Vec<LineInfo>> {
+        crate::layout::layout_inline_with_lines(
+            &self.children,
+            engine,
+            locator,
+            styles,
+            consecutive,
+            region,
+            expand,
+        )
+        .map(|(_, lines)| lines)
+    }
+
+    /// Determine the words that are hyphenated in the paragraph, e.g. to
+    /// proofread them.
+    ///
+    /// The words are returned in the order of the lines they end, together
+    /// with the position at which they were broken. Like
+    /// [`lines`](Self::lines), this reuses the memoized paragraph layout.
+    pub fn hyphenations(
+        &self,
+        engine: &mut Engine,
+        locator: Locator,
+        styles: StyleChain,
+        consecutive: bool,
+        region: Size,
+        expand: bool,
+    ) -> SourceResult<Vec<Hyphenation>> {
+        let lines = self.lines(engine, locator, styles, consecutive, region, expand)?;
+        Ok(lines.into_iter().filter_map(|line| line.hyphenation).collect())
+    }
+
+    /// Measure trial lines spanning byte ranges of the paragraph's text.
+    ///
+    /// This is the intended way to cheaply probe many candidate ranges, for
+    /// example when sizing table columns. The lines are broken and shaped,
+    /// but their frames are not built, which is the expensive step of
+    /// committing a line. Each line is measured as if it was broken at the end
+    /// of its range. Ranges that don't fall on character boundaries are
+    /// widened to the nearest ones.
+    ///
+    /// The paragraph is only prepared once for all ranges passed together.
+    pub fn measure_lines(
+        &self,
+        engine: &mut Engine,
+        locator: Locator,
+        styles: StyleChain,
+        consecutive: bool,
+        region: Size,
+        ranges: Vec<std::ops::Range<usize>>,
+    ) -> SourceResult<Vec<LineMetrics>> {
+        crate::layout::measure_inline_lines(
+            &self.children,
+            engine,
+            locator,
+            styles,
+            consecutive,
+            region,
+            ranges,
+        )
+    }
+
+    /// Determine where the glyphs for a byte range of the paragraph's text
+    /// end up, e.g. to highlight a text selection or search result.
+    ///
+    /// For each line, as returned by [`lines`](Self::lines), this returns the
+    /// bounding rectangle of each glyph in the range, relative to the line's
+    /// frame. Vertically, the rectangles span from the top to the bottom edge
+    /// of the glyph's text. The glyphs are in visual order, so a range
+    /// spanning text of different directions may yield multiple disjoint
+    /// groups.
+    #[allow(clippy::too_many_arguments)]
+    pub fn glyph_extents(
+        &self,
+        engine: &mut Engine,
+        locator: Locator,
+        styles: StyleChain,
+        consecutive: bool,
+        region: Size,
+        expand: bool,
+        range: std::ops::Range<usize>,
+    ) -> SourceResult<Vec<Vec<GlyphExtent>>> {
+        crate::layout::locate_inline_glyphs(
+            &self.children,
+            engine,
+            locator,
+            styles,
+            consecutive,
+            region,
+            expand,
+            range,
+        )
+    }
+
+    /// Determine the bidirectional structure of a byte range of the
+    /// paragraph's text.
+    ///
+    /// Returns the runs of the range that share an embedding level, in
+    /// logical order, together with their direction and the base direction
+    /// of the BiDi paragraph they belong to. This is useful to match the
+    /// direction of surrounding content to the text, e.g. to place margin
+    /// notes on the side where reading starts.
+    pub fn bidi_runs(
+        &self,
+        engine: &mut Engine,
+        locator: Locator,
+        styles: StyleChain,
+        consecutive: bool,
+        region: Size,
+        range: std::ops::Range<usize>,
+    ) -> SourceResult<Vec<BidiRun>> {
+        crate::layout::analyze_inline_bidi(
+            &self.children,
+            engine,
+            locator,
+            styles,
+            consecutive,
+            region,
+            range,
+        )
+    }
+
+    /// Determine the paragraph's minimum and maximum intrinsic widths.
+    ///
+    /// The minimum is the width of the widest piece of text that cannot be
+    /// broken and the maximum is the width of the widest line if the
+    /// paragraph is only broken where it must be. The region is only used to
+    /// resolve relative sizes of inline content.
+    pub fn intrinsic_widths(
+        &self,
+        engine: &mut Engine,
+        locator: Locator,
+        styles: StyleChain,
+        consecutive: bool,
+        region: Size,
+    ) -> SourceResult<(Abs, Abs)> {
+        crate::layout::measure_inline(
+            &self.children,
+            engine,
+            locator,
+            styles,
+            consecutive,
+            region,
+        )
+    }
 }
 
 impl Debug for ParElem {
@@ -200,6 +1100,113 @@ pub enum Linebreaks {
     /// Typst will try to produce more evenly filled lines of text by
     /// considering the whole paragraph when calculating line breaks.
     Optimized,
+    /// Make all lines about equally long.
+    ///
+    /// Instead of filling each line as much as possible, Typst keeps the
+    /// number of lines of the simple layout, but distributes the text evenly
+    /// among them. This gives ragged paragraphs like short captions or pull
+    /// quotes an even right edge. It is not intended for justified text.
+    Balanced,
+}
+
+/// What to do with lines that are wider than their paragraph.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Cast)]
+pub enum Overflow {
+    /// The line overflows the paragraph.
+    #[default]
+    Visible,
+    /// The line is cut off at the paragraph's edges.
+    Clip,
+    /// The line is shortened to fit and ends with an ellipsis.
+    Truncate,
+}
+
+/// Which paragraphs to keep from breaking across regions.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum KeepTogether {
+    /// Keep paragraphs with at most this many lines together.
+    Lines(usize),
+    /// Keep all paragraphs together.
+    All,
+}
+
+impl KeepTogether {
+    /// Whether a paragraph with the given number of lines is kept together.
+    pub fn applies(self, lines: usize) -> bool {
+        match self {
+            Self::Lines(max) => lines <= max,
+            Self::All => true,
+        }
+    }
+}
+
+impl Default for KeepTogether {
+    fn default() -> Self {
+        Self::Lines(0)
+    }
+}
+
+cast! {
+    KeepTogether,
+    self => match self {
+        Self::Lines(0) => false.into_value(),
+        Self::Lines(max) => max.into_value(),
+        Self::All => true.into_value(),
+    },
+    v: bool => if v { Self::All } else { Self::Lines(0) },
+    v: usize => Self::Lines(v),
+}
+
+/// Words with explicit hyphenation points, grouped by language.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct HyphenationExceptions(Vec<(Lang, Vec<EcoString>)>);
+
+impl HyphenationExceptions {
+    /// The character positions at which the given word may be hyphenated, if
+    /// it has an exception in the given language.
+    ///
+    /// Characters are compared one by one regardless of case, so that the
+    /// positions stay valid for the word even if lowercasing would change the
+    /// number of characters.
+    pub fn get(&self, lang: Lang, word: &str) -> Option<Vec<usize>> {
+        let (_, words) = self.0.iter().find(|(l, _)| *l == lang)?;
+        let exception = words.iter().find(|exception| {
+            let letters = exception.chars().filter(|&c| c != '-');
+            letters.clone().count() == word.chars().count()
+                && letters
+                    .zip(word.chars())
+                    .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+        })?;
+
+        let mut breaks = vec![];
+        let mut count = 0;
+        for c in exception.chars() {
+            if c == '-' {
+                breaks.push(count);
+            } else {
+                count += 1;
+            }
+        }
+
+        Some(breaks)
+    }
+}
+
+cast! {
+    HyphenationExceptions,
+    self => self.0
+        .into_iter()
+        .map(|(lang, words)| (lang.as_str().into(), words.into_value()))
+        .collect::<Dict>()
+        .into_value(),
+    values: Dict => Self(values
+        .into_iter()
+        .map(|(k, v)| {
+            let lang = Lang::from_str(&k)?;
+            let words = v.cast::<Vec<EcoString>>()?;
+            Ok((lang, words))
+        })
+        .collect::<HintedStrResult<_>>()?),
 }
 
 /// A paragraph break.
@@ -224,3 +1231,21 @@ pub enum Linebreaks {
 pub struct ParbreakElem {}
 
 impl Unlabellable for Packed<ParbreakElem> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyphenation_exceptions() {
+        let exceptions =
+            HyphenationExceptions(vec![(Lang::ENGLISH, vec!["İs-tan-bul".into()])]);
+        assert_eq!(exceptions.get(Lang::ENGLISH, "İSTANBUL"), Some(vec![2, 5]));
+        assert_eq!(exceptions.get(Lang::GERMAN, "İstanbul"), None);
+
+        // Lowercasing `İ` yields two characters, so counting breaks in the
+        // lowercased word would misplace them.
+        assert_eq!(exceptions.get(Lang::ENGLISH, "i\u{307}stanbul"), None);
+        assert_eq!(exceptions.get(Lang::ENGLISH, "istanbul"), None);
+    }
+}
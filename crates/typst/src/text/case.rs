@@ -1,5 +1,5 @@
 use crate::foundations::{cast, func, Cast, Content, Str};
-use crate::text::TextElem;
+use crate::text::{Lang, TextElem};
 
 /// Converts a string or content to lowercase.
 ///
@@ -36,7 +36,7 @@ pub fn upper(
 /// Change the case of text.
 fn case(text: Caseable, case: Case) -> Caseable {
     match text {
-        Caseable::Str(v) => Caseable::Str(case.apply(&v).into()),
+        Caseable::Str(v) => Caseable::Str(case.apply(&v, None, None).into()),
         Caseable::Content(v) => {
             Caseable::Content(v.styled(TextElem::set_case(Some(case))))
         }
@@ -66,14 +66,46 @@ pub enum Case {
     Lower,
     /// Everything is uppercased.
     Upper,
+    /// The first letter of each word is uppercased.
+    Title,
 }
 
 impl Case {
     /// Apply the case to a string.
-    pub fn apply(self, text: &str) -> String {
+    ///
+    /// The `lang` selects language-specific mappings, like the dotted and
+    /// dotless i in Turkish. The `prev` character is the one preceding the
+    /// string, if any, and determines whether it starts within a word.
+    pub fn apply(self, text: &str, lang: Option<Lang>, prev: Option<char>) -> String {
+        let turkic = matches!(lang, Some(Lang::TURKISH | Lang::AZERBAIJANI));
         match self {
+            Self::Lower if turkic => {
+                text.replace('I', "ı").replace('İ', "i").to_lowercase()
+            }
             Self::Lower => text.to_lowercase(),
+            Self::Upper if turkic => text.replace('i', "İ").to_uppercase(),
             Self::Upper => text.to_uppercase(),
+            Self::Title => {
+                let mut out = String::with_capacity(text.len());
+                let mut start = prev.map_or(true, |c| !is_word_char(c));
+                for c in text.chars() {
+                    if start && c.is_alphabetic() {
+                        match c {
+                            'i' if turkic => out.push('İ'),
+                            _ => out.extend(c.to_uppercase()),
+                        }
+                    } else {
+                        out.push(c);
+                    }
+                    start = !is_word_char(c);
+                }
+                out
+            }
         }
     }
 }
+
+/// Whether the character continues a word for the purposes of title case.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '\'' | '’')
+}
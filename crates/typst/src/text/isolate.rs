@@ -0,0 +1,52 @@
+use crate::diag::SourceResult;
+use crate::engine::Engine;
+use crate::foundations::{elem, Content, Packed, Show, Smart, StyleChain};
+use crate::layout::Dir;
+use crate::text::{TextDir, TextElem};
+
+/// Isolates text from the bidirectional ordering of its surroundings.
+///
+/// The content is wrapped in Unicode directional isolate characters. It is
+/// then ordered as a whole with the text around it, while its own text is
+/// ordered independently. This is useful to embed a phrase whose direction
+/// differs from the paragraph, such as an English title in Arabic text. In
+/// contrast to setting the [text direction]($text.dir), neutral characters
+/// like punctuation at the edges of the content don't interact with the
+/// surrounding text.
+///
+/// The isolate characters themselves are invisible and take up no space.
+///
+/// # Example
+/// ```example
+/// #set text(font: ("PT Sans", "Noto Sans Arabic"), lang: "ar")
+/// قرأت #isolate(dir: ltr)[The C Book!] أمس
+/// ```
+#[elem(Show)]
+pub struct IsolateElem {
+    /// The direction of the isolated content.
+    ///
+    /// When set to `{auto}`, the direction is determined from the first
+    /// character in the content that has a strong direction.
+    pub dir: TextDir,
+
+    /// The content to isolate.
+    #[required]
+    pub body: Content,
+}
+
+impl Show for Packed<IsolateElem> {
+    #[typst_macros::time(name = "isolate", span = self.span())]
+    fn show(&self, _: &mut Engine, styles: StyleChain) -> SourceResult<Content> {
+        let open = match self.dir(styles).0 {
+            Smart::Custom(Dir::RTL) => "\u{2067}", // Right-to-Left Isolate
+            Smart::Custom(_) => "\u{2066}",        // Left-to-Right Isolate
+            Smart::Auto => "\u{2068}",             // First Strong Isolate
+        };
+
+        Ok(Content::sequence([
+            TextElem::packed(open),
+            self.body().clone(),
+            TextElem::packed("\u{2069}"), // Pop Directional Isolate
+        ]))
+    }
+}
@@ -57,6 +57,7 @@ pub struct Lang([u8; 3], u8);
 impl Lang {
     pub const ALBANIAN: Self = Self(*b"sq ", 2);
     pub const ARABIC: Self = Self(*b"ar ", 2);
+    pub const AZERBAIJANI: Self = Self(*b"az ", 2);
     pub const BOKMÅL: Self = Self(*b"nb ", 2);
     pub const CATALAN: Self = Self(*b"ca ", 2);
     pub const CHINESE: Self = Self(*b"zh ", 2);
@@ -98,8 +99,8 @@ impl Lang {
     /// The default direction for the language.
     pub fn dir(self) -> Dir {
         match self.as_str() {
-            "ar" | "dv" | "fa" | "he" | "ks" | "pa" | "ps" | "sd" | "ug" | "ur"
-            | "yi" => Dir::RTL,
+            "ar" | "arc" | "ckb" | "dv" | "fa" | "he" | "ks" | "nqo" | "pa" | "ps"
+            | "sd" | "syr" | "ug" | "ur" | "yi" => Dir::RTL,
             _ => Dir::LTR,
         }
     }
@@ -195,6 +196,18 @@ impl WritingScript {
     pub fn as_bytes(&self) -> &[u8; 4] {
         &self.0
     }
+
+    /// The default direction for the script.
+    pub fn dir(self) -> Dir {
+        match self.as_str() {
+            "adlm" | "arab" | "armi" | "avst" | "chrs" | "cprt" | "elym" | "hatr"
+            | "hebr" | "hung" | "khar" | "lydi" | "mand" | "mani" | "mend" | "merc"
+            | "mero" | "narb" | "nbat" | "nkoo" | "orkh" | "ougr" | "palm" | "phli"
+            | "phlp" | "phnx" | "prti" | "rohg" | "samr" | "sarb" | "sogd" | "sogo"
+            | "syrc" | "thaa" | "yezi" => Dir::RTL,
+            _ => Dir::LTR,
+        }
+    }
 }
 
 impl FromStr for WritingScript {
@@ -307,4 +320,14 @@ mod tests {
         assert!(option_eq(region, "US"));
         assert!(!option_eq(region, "AB"));
     }
+
+    #[test]
+    fn test_script_dir() {
+        let dir = |iso| WritingScript::from_str(iso).unwrap().dir();
+        assert_eq!(dir("hebr"), Dir::RTL);
+        assert_eq!(dir("Thaa"), Dir::RTL);
+        assert_eq!(dir("nkoo"), Dir::RTL);
+        assert_eq!(dir("latn"), Dir::LTR);
+        assert_eq!(dir("math"), Dir::LTR);
+    }
 }
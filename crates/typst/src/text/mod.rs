@@ -3,12 +3,14 @@
 mod case;
 mod deco;
 mod font;
+mod isolate;
 mod item;
 mod lang;
 mod linebreak;
 #[path = "lorem.rs"]
 mod lorem_;
 mod raw;
+mod ruby;
 mod shift;
 #[path = "smallcaps.rs"]
 mod smallcaps_;
@@ -18,11 +20,13 @@ mod space;
 pub use self::case::*;
 pub use self::deco::*;
 pub use self::font::*;
+pub use self::isolate::*;
 pub use self::item::*;
 pub use self::lang::*;
 pub use self::linebreak::*;
 pub use self::lorem_::*;
 pub use self::raw::*;
+pub use self::ruby::*;
 pub use self::shift::*;
 pub use self::smallcaps_::*;
 pub use self::smartquote::*;
@@ -67,6 +71,8 @@ pub(super) fn define(global: &mut Scope) {
     global.define_elem::<StrikeElem>();
     global.define_elem::<HighlightElem>();
     global.define_elem::<SmallcapsElem>();
+    global.define_elem::<RubyElem>();
+    global.define_elem::<IsolateElem>();
     global.define_elem::<RawElem>();
     global.define_func::<lower>();
     global.define_func::<upper>();
@@ -225,6 +231,26 @@ pub struct TextElem {
     #[ghost]
     pub stretch: FontStretch,
 
+    /// Whether to synthesize bold and italic glyphs when the selected font
+    /// doesn't provide the requested [weight]($text.weight) or
+    /// [style]($text.style).
+    ///
+    /// When set to `{auto}`, glyphs of a font that is too light are emboldened
+    /// by stroking them with their fill and glyphs of an upright font are
+    /// slanted to mimic italics. This is especially useful for CJK fonts, which
+    /// often ship with a single weight only. With `{none}`, the regular face is
+    /// used as is.
+    ///
+    /// ```example
+    /// #set text(font: "Noto Serif CJK SC")
+    /// 常规 *粗体* _斜体_ \
+    /// #set text(synthesis: auto)
+    /// 常规 *粗体* _斜体_
+    /// ```
+    #[ghost]
+    #[default(Smart::Custom(None))]
+    pub synthesis: Smart<Option<Never>>,
+
     /// The size of the glyphs. This value forms the basis of the `em` unit:
     /// `{1em}` is equivalent to the font size.
     ///
@@ -436,7 +462,8 @@ pub struct TextElem {
 
     /// The dominant direction for text and inline objects. Possible values are:
     ///
-    /// - `{auto}`: Automatically infer the direction from the `lang` property.
+    /// - `{auto}`: Automatically infer the direction from the `script`
+    ///   property if it is set and from the `lang` property otherwise.
     /// - `{ltr}`: Layout text from left to right.
     /// - `{rtl}`: Layout text from right to left.
     ///
@@ -485,6 +512,22 @@ pub struct TextElem {
     #[ghost]
     pub hyphenate: Hyphenate,
 
+    /// Whether to prevent line breaks within the text.
+    ///
+    /// Text with this property set to `{true}` is kept on a single line. This
+    /// is useful for short phrases like figure references or phone numbers
+    /// that should not be torn apart. Line breaks between such text and its
+    /// surroundings are still allowed.
+    ///
+    /// ```example
+    /// #set page(width: 120pt)
+    /// As shown in #text(nobreak: true)[Figure 3], the
+    /// results are conclusive.
+    /// ```
+    #[ghost]
+    #[default(false)]
+    pub nobreak: bool,
+
     /// The "cost" of various choices when laying out text. A higher cost means
     /// the layout engine will make the choice less often. Costs are specified
     /// as a ratio of the default cost, so `50%` will make text layout twice as
@@ -495,6 +538,8 @@ pub struct TextElem {
     /// - `runt`: ending a paragraph with a line with a single word
     /// - `widow`: leaving a single line of paragraph on the next page
     /// - `orphan`: leaving single line of paragraph on the previous page
+    /// - `river`: aligning the gaps between words in consecutive justified
+    ///   lines, forming a vertical stream of whitespace
     ///
     /// Hyphenation is generally avoided by placing the whole word on the next
     /// line, so a higher hyphenation cost can result in awkward justification
@@ -512,6 +557,13 @@ pub struct TextElem {
     /// default of `auto`, prevents them. More nuanced cost specification for
     /// these modifications is planned for the future.)
     ///
+    /// Rivers are only considered when lines are broken with the `{"optimized"}`
+    /// [strategy]($par.linebreaks). In contrast to the other costs, the river
+    /// cost defaults to `{0%}`, so they are permitted unless requested
+    /// otherwise. With a positive cost, the line breaker prefers breakpoints
+    /// for which fewer gaps in a line lie directly below gaps in the previous
+    /// line.
+    ///
     /// The default costs are an acceptable balance, but some may find that it
     /// hyphenates or avoids runs too eagerly, breaking the flow of dense prose.
     /// A cost of 600% (six times the normal cost) may work better for such
@@ -714,16 +766,27 @@ pub struct TextElem {
     #[ghost]
     pub deco: SmallVec<[Decoration; 1]>,
 
-    /// A case transformation that should be applied to the text.
-    #[internal]
+    /// A case transformation to apply to the text.
+    ///
+    /// The transformation takes the [language]($text.lang) into account, for
+    /// instance for the dotted and dotless i in Turkish. Unlike the
+    /// [`upper`] and [`lower`] functions, this can be used in a set rule to
+    /// transform all text of, for example, a heading.
+    ///
+    /// ```example
+    /// #show heading: set text(case: "upper")
+    /// = Introduction
+    ///
+    /// #text(case: "title")[the art of typesetting] \
+    /// #text(lang: "tr", case: "upper")[istanbul]
+    /// ```
     #[ghost]
     pub case: Option<Case>,
 
-    /// Whether small capital glyphs should be used. ("smcp")
+    /// Whether small capital glyphs should be used. ("smcp", "c2sc")
     #[internal]
-    #[default(false)]
     #[ghost]
-    pub smallcaps: bool,
+    pub smallcaps: Option<Smallcaps>,
 }
 
 impl TextElem {
@@ -1037,7 +1100,10 @@ impl Resolve for TextDir {
 
     fn resolve(self, styles: StyleChain) -> Self::Output {
         match self.0 {
-            Smart::Auto => TextElem::lang_in(styles).dir(),
+            Smart::Auto => match TextElem::script_in(styles) {
+                Smart::Custom(script) => script.dir(),
+                Smart::Auto => TextElem::lang_in(styles).dir(),
+            },
             Smart::Custom(dir) => dir,
         }
     }
@@ -1160,8 +1226,11 @@ pub(crate) fn features(styles: StyleChain) -> Vec<Feature> {
     }
 
     // Features that are off by default in Harfbuzz are only added if enabled.
-    if TextElem::smallcaps_in(styles) {
+    if let Some(sc) = TextElem::smallcaps_in(styles) {
         feat(b"smcp", 1);
+        if sc == Smallcaps::All {
+            feat(b"c2sc", 1);
+        }
     }
 
     if TextElem::alternates_in(styles) {
@@ -1244,6 +1313,7 @@ pub struct Costs {
     runt: Option<Ratio>,
     widow: Option<Ratio>,
     orphan: Option<Ratio>,
+    river: Option<Ratio>,
 }
 
 impl Costs {
@@ -1266,6 +1336,11 @@ impl Costs {
     pub fn orphan(&self) -> Ratio {
         self.orphan.unwrap_or(Ratio::one())
     }
+
+    #[must_use]
+    pub fn river(&self) -> Ratio {
+        self.river.unwrap_or(Ratio::zero())
+    }
 }
 
 impl Fold for Costs {
@@ -1276,6 +1351,7 @@ impl Fold for Costs {
             runt: self.runt.or(outer.runt),
             widow: self.widow.or(outer.widow),
             orphan: self.orphan.or(outer.orphan),
+            river: self.river.or(outer.river),
         }
     }
 }
@@ -1287,6 +1363,7 @@ cast! {
         "runt" => self.runt(),
         "widow" => self.widow(),
         "orphan" => self.orphan(),
+        "river" => self.river(),
     ].into_value(),
     mut v: Dict => {
         let ret = Self {
@@ -1294,8 +1371,9 @@ cast! {
             runt: v.take("runt").ok().map(|v| v.cast()).transpose()?,
             widow: v.take("widow").ok().map(|v| v.cast()).transpose()?,
             orphan: v.take("orphan").ok().map(|v| v.cast()).transpose()?,
+            river: v.take("river").ok().map(|v| v.cast()).transpose()?,
         };
-        v.finish(&["hyphenation", "runt", "widow", "orphan"])?;
+        v.finish(&["hyphenation", "runt", "widow", "orphan", "river"])?;
         ret
     },
 }
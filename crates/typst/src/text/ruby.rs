@@ -0,0 +1,98 @@
+use crate::diag::SourceResult;
+use crate::engine::Engine;
+use crate::foundations::{elem, Content, Packed, Resolve, Show, StyleChain};
+use crate::introspection::Locator;
+use crate::layout::{Abs, Axes, Em, Frame, InlineElem, InlineItem, Point, Regions, Size};
+use crate::text::{TextElem, TextSize};
+
+/// Annotates text with small ruby text above it.
+///
+/// Ruby is commonly used for Japanese furigana and Chinese pinyin to indicate
+/// the pronunciation of the base text. The annotation is centered above the
+/// base and the two are kept together on one line.
+///
+/// # Example
+/// ```example
+/// #set text(font: "Noto Serif CJK JP", lang: "ja")
+/// #ruby[東京][とうきょう]に#ruby[行][い]く。
+/// ```
+#[elem(Show)]
+pub struct RubyElem {
+    /// The font size of the annotation.
+    #[default(TextSize(Em::new(0.5).into()))]
+    pub size: TextSize,
+
+    /// Whether an annotation that is wider than its base may extend over the
+    /// adjacent text.
+    ///
+    /// If enabled, the annotation may overhang each side of its base by up to
+    /// one annotation character, which avoids gaps between the base and the
+    /// surrounding text. Otherwise, the base is spaced out to the width of
+    /// the annotation.
+    ///
+    /// ```example
+    /// #set text(font: "Noto Serif CJK JP", lang: "ja")
+    /// の#ruby[志][こころざし]を
+    ///
+    /// #set ruby(overhang: false)
+    /// の#ruby[志][こころざし]を
+    /// ```
+    #[default(true)]
+    pub overhang: bool,
+
+    /// The base text to annotate.
+    #[required]
+    pub base: Content,
+
+    /// The annotation to display above the base.
+    #[required]
+    pub annotation: Content,
+}
+
+impl Show for Packed<RubyElem> {
+    #[typst_macros::time(name = "ruby", span = self.span())]
+    fn show(&self, _: &mut Engine, _: StyleChain) -> SourceResult<Content> {
+        Ok(InlineElem::layouter(self.clone(), layout_ruby)
+            .pack()
+            .spanned(self.span()))
+    }
+}
+
+/// Layout the base and its annotation into one unbreakable inline frame.
+fn layout_ruby(
+    elem: &Packed<RubyElem>,
+    engine: &mut Engine,
+    locator: Locator,
+    styles: StyleChain,
+    region: Size,
+) -> SourceResult<Vec<InlineItem>> {
+    let mut locator = locator.split();
+    let pod = Regions::one(region, Axes::splat(false));
+    let size = elem.size(styles);
+
+    let base = elem
+        .base()
+        .layout(engine, locator.next(&()), styles, pod)?
+        .into_frame();
+    let annotation = elem
+        .annotation()
+        .clone()
+        .styled(TextElem::set_size(size))
+        .layout(engine, locator.next(&()), styles, pod)?
+        .into_frame();
+
+    // The annotation may extend over the adjacent text by up to one of its
+    // characters on each side.
+    let overhang =
+        if elem.overhang(styles) { size.0.resolve(styles) } else { Abs::zero() };
+    let width = base.width().max(annotation.width() - 2.0 * overhang);
+
+    let mut frame = Frame::soft(Size::new(width, annotation.height() + base.height()));
+    frame.set_baseline(annotation.height() + base.baseline());
+
+    let annotation_height = annotation.height();
+    frame.push_frame(Point::with_x((width - annotation.width()) / 2.0), annotation);
+    frame.push_frame(Point::new((width - base.width()) / 2.0, annotation_height), base);
+
+    Ok(vec![InlineItem::Frame(frame)])
+}
@@ -9,6 +9,27 @@ Spaced \
 #box(height: 0.5cm) \
 Apart
 
+--- box-valign ---
+// Test that boxes aligned relative to the line grow it to fit exactly.
+#context {
+  let tall = rect(height: 3em)
+  for align in (top, horizon, bottom) {
+    let size = measure(block[A #box(valign: align, tall) B])
+    test(size.height, measure(tall).height)
+  }
+}
+
+--- box-line-overflow ---
+// Test that overlapping and clipped boxes don't make their line taller.
+#context {
+  let tall = rect(height: 3em)
+  let line = measure(block[A B]).height
+  for overflow in ("overlap", "clip") {
+    let size = measure(block[A #box(line-overflow: overflow, tall) B])
+    test(size.height, line)
+  }
+}
+
 --- block-sizing ---
 // Test block sizing.
 #set page(height: 120pt)
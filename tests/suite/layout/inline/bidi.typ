@@ -43,6 +43,13 @@ Lריווח #h(1cm) R
 #set text(lang: "he")
 קרנפיםRh#box(image("/assets/images/rhino.png", height: 11pt))inoחיים
 
+--- bidi-obj-between-numbers ---
+// Test that inline objects between Arabic text and digits are ordered like the
+// Arabic text around them.
+#set text(lang: "ar", font: ("Libertinus Serif", "Noto Sans Arabic"))
+#place(hide[عدد #box(width: 5pt)<a>#box(width: 5pt)<b> 123])
+#context test(locate(<a>).position().x > locate(<b>).position().x, true)
+
 --- bidi-whitespace-reset ---
 // Test whether L1 whitespace resetting destroys stuff.
 الغالب #h(70pt) ن#" "ة
@@ -54,6 +61,33 @@ Lריווח #h(1cm) R
 #linebreak()
 ב #text("12:00 - 13:00", dir: ltr) בצהריים
 
+--- bidi-isolate-zero-width ---
+// Test that the isolate characters take up no space.
+#context {
+  let body = [Text טֶקסט]
+  for dir in (auto, ltr, rtl) {
+    test(measure(isolate(dir: dir, body)).width, measure(body).width)
+  }
+}
+
+--- bidi-isolate-vertical ---
+// Error: 19-22 text direction must be horizontal
+#isolate(dir: ttb)[A]
+
+--- bidi-box-direction ---
+// Test that inline boxes take on the direction of the text around them.
+#box[#context test(text.dir, auto)]
+טֶקסט #box[#context test(text.dir, rtl)]
+Text #box[#context test(text.dir, auto)]
+
+--- bidi-box-direction-isolates ---
+// Test that closed isolates are skipped when determining the direction of an
+// inline box, while embeddings determine it themselves.
+טֶקסט \u{2066}Text\u{2069} #box[#context test(text.dir, rtl)]
+Text \u{202B}טֶקסט\u{202C} #box[#context test(text.dir, rtl)]
+Text \u{2067}#box[#context test(text.dir, rtl)]\u{2069}
+Text \u{202B}#box[#context test(text.dir, rtl)]\u{202C}
+
 --- bidi-raw ---
 // Mixing raw
 #set text(lang: "he")
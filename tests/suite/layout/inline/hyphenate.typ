@@ -41,6 +41,17 @@ It's a #emph[Tree]beard.
   [Bar-?ankauf],
 )
 
+--- hyphenate-shy-ligature ---
+// Test that lines broken at a soft hyphen within a ligature are shaped as if
+// the parts were separate.
+#context {
+  let width(body) = measure(body).width
+  let broken(body) = measure(width: 1pt, par(hyphen: none, body)).width
+  test(broken[of-?fice], calc.max(width[of], width[fice]))
+  test(broken[of-?f-?ice], calc.max(width[of], width[f], width[ice]))
+  test(broken[f-?i], calc.max(width[f], width[i]))
+}
+
 --- hyphenate-punctuation ---
 // This sequence would confuse hypher if we passed trailing / leading
 // punctuation instead of just the words. So this tests that we don't
@@ -112,6 +123,81 @@ Tras el estallido de la contienda Ruiz-Giménez fue detenido junto a sus
 dos hermanos y puesto bajo custodia por las autoridades republicanas, con
 el objetivo de protegerle de las patrullas de milicianos.
 
+--- hyphenate-before-after ---
+// Test that breaks too close to either end of a word are filtered out.
+#set text(hyphenate: true)
+#set par(hyphenate-last-word: true)
+#context {
+  let height(..args) = measure(width: 30pt, par(..args)[manuscript]).height
+  let single = measure[manuscript].height
+  assert(height() > single)
+  assert(height(hyphenate-before: 3, hyphenate-after: 3) > single)
+  test(height(hyphenate-before: 11), single)
+  test(height(hyphenate-after: 11), single)
+}
+
+--- hyphenate-lines ---
+// Test that both line breakers respect the limit of consecutive hyphenated
+// lines.
+#set par(justify: true)
+#set text(costs: (runt: 0%))
+#context {
+  for mode in ("simple", "optimized") {
+    let size(..args) = measure(width: 80pt, par(linebreaks: mode, ..args))
+    let plain = size(text(hyphenate: false, lorem(40)))
+    assert.ne(size(lorem(40)), plain)
+    test(size(hyphenate-lines: 0, lorem(40)), plain)
+  }
+}
+
+--- hyphenate-exceptions ---
+// Test that exceptions replace the patterns regardless of case and only
+// apply to their language.
+#set text(hyphenate: true)
+#set par(hyphenate-last-word: true)
+#context {
+  let width(body) = measure(body).width
+  let broken(..args) = measure(width: 1pt, par(..args)[Manuscript]).width
+  let exceptions = (en: ("manu-script",), de: ("m-anuscript",))
+  test(broken(hyphenate-exceptions: exceptions), calc.max(width[Manu-], width[script]))
+  test(broken(hyphenate-exceptions: (en: ("manuscript",))), width[Manuscript])
+}
+
+--- hyphenate-exceptions-invalid-lang ---
+// Error: 31-50 expected two or three letter language code (ISO 639-1/2/3)
+#set par(hyphenate-exceptions: (english: ("a-b",)))
+
+--- hyphenate-last-word ---
+// Test that the last word of a paragraph is only hyphenated if allowed.
+#set text(hyphenate: true)
+#context {
+  let broken(..args) = measure(width: 1pt, par(..args)[A manuscript]).width
+  test(broken(), measure[manuscript].width)
+  assert(broken(hyphenate-last-word: true) < measure[manuscript].width)
+}
+
+--- hyphenate-capitalized ---
+// Test that capitalized words are only hyphenated if allowed.
+#set text(hyphenate: true)
+#set par(hyphenate-last-word: true)
+#context {
+  let broken(body) = measure(width: 1pt, par(hyphenate-capitalized: false, body))
+  test(broken[Manuscript].width, measure[Manuscript].width)
+  assert(broken[manuscript].width < measure[manuscript].width)
+}
+
+--- hyphenate-ragged-tolerance ---
+// Test that a full tolerance prevents all hyphenation in ragged paragraphs,
+// while no tolerance keeps it.
+#set text(hyphenate: true)
+#set par(linebreaks: "simple")
+#context {
+  let size(..args) = measure(width: 80pt, par(..args, lorem(30)))
+  let plain = measure(width: 80pt, text(hyphenate: false, lorem(30)))
+  test(size(ragged-tolerance: 100%), plain)
+  assert.ne(size(ragged-tolerance: none), plain)
+}
+
 --- costs-widow-orphan ---
 #set page(height: 60pt)
 
@@ -153,17 +239,51 @@ el objetivo de protegerle de las patrullas de milicianos.
 #set text(costs: (hyphenation: 10000%))
 #sample
 
+--- costs-river ---
+#set par(justify: true)
+
+#let sample = lorem(40)
+
+// Rivers are permitted by default.
+#context test(
+  measure(width: 120pt, text(costs: (river: 0%), sample)),
+  measure(width: 120pt, sample),
+)
+
+--- costs-river-avoid ---
+// Test that a river cost moves the breaks for at least some widths. Each word
+// is followed by a marker so that we can compare where the lines break.
+#set par(justify: true)
+#let widths = range(80, 200, step: 5)
+#let sample(kind, width) = {
+  lorem(60).split(" ").map(word => [#word#metadata(width)#kind]).join(" ")
+}
+#for width in widths {
+  place(hide(block(width: width * 1pt, sample(<plain>, width))))
+  place(hide(block(
+    width: width * 1pt,
+    text(costs: (river: 10000%), sample(<river>, width)),
+  )))
+}
+#context {
+  let positions(kind, width) = {
+    let found = query(kind).filter(it => it.value == width)
+    found.map(it => it.location().position())
+  }
+  assert(widths.any(width => positions(<plain>, width) != positions(<river>, width)))
+}
+
 --- costs-invalid-type ---
 // Error: 18-37 expected ratio, found auto
 #set text(costs: (hyphenation: auto))
 
 --- costs-invalid-key ---
-// Error: 18-52 unexpected key "invalid-key", valid keys are "hyphenation", "runt", "widow", and "orphan"
+// Error: 18-52 unexpected key "invalid-key", valid keys are "hyphenation", "runt", "widow", "orphan", and "river"
 #set text(costs: (hyphenation: 1%, invalid-key: 3%))
 
 --- costs-access ---
 #set text(costs: (hyphenation: 1%, runt: 2%))
 #set text(costs: (widow: 3%))
 #context {
-  assert.eq(text.costs, (hyphenation: 1%, runt: 2%, widow: 3%, orphan: 100%))
+  assert.eq(text.costs, (hyphenation: 1%, runt: 2%, widow: 3%, orphan: 100%, river: 0%))
 }
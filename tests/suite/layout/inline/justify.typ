@@ -51,6 +51,29 @@ D
 A B C #linebreak(justify: true)
 D E F #linebreak(justify: true)
 
+--- justify-last ---
+// Test that the last line is only stretched to the full width if requested.
+#set par(justify: true)
+#place(hide(block(width: 100pt)[A B#box()<plain>]))
+#place(hide(block(width: 100pt, par(justify-last: true)[A B#box()<full>])))
+#context {
+  assert(locate(<plain>).position().x < 60pt)
+  assert(calc.abs((locate(<full>).position().x - 110pt).pt()) < 0.01)
+}
+
+--- justify-nbsp ---
+// Test that non-breaking spaces keep their natural width when justifying,
+// while regular spaces are stretched.
+#set par(justify: true)
+#place(hide(block(width: 100pt)[A~#box()<nbsp> #lorem(10)]))
+#place(hide(block(width: 100pt, par(justify: false)[A~#box()<nbsp-ragged> #lorem(10)])))
+#place(hide(block(width: 100pt)[A #box()<space> #lorem(10)]))
+#place(hide(block(width: 100pt, par(justify: false)[A #box()<space-ragged> #lorem(10)])))
+#context {
+  test(locate(<nbsp>).position().x, locate(<nbsp-ragged>).position().x)
+  assert(locate(<space>).position().x > locate(<space-ragged>).position().x)
+}
+
 --- justify-basically-empty ---
 // Test that there are no hick-ups with justification enabled and
 // basically empty paragraph.
@@ -63,6 +86,111 @@ D E F #linebreak(justify: true)
 #set par(justify: true)
 This text can be fitted in one line.
 
+--- justify-shrink-limit ---
+// Test that a slightly overlong line fits only if spaces may shrink enough.
+#set par(justify: true)
+#set text(hyphenate: false)
+#context {
+  let body = [Shrink these words]
+  let width = measure(body).width - 1pt
+  for mode in ("simple", "optimized") {
+    let fit(..args) = measure(width: width, par(linebreaks: mode, ..args, body))
+    test(fit().width, width)
+    test(fit(justify-shrink: 0%).width, measure[Shrink these].width)
+  }
+}
+
+--- justify-stretch ---
+// Test that word spaces stretch only up to the limit, while the justification
+// of Chinese text is not limited.
+#set par(justify: true)
+#let latin(label, ..args) = place(hide(block(width: 100pt, par(..args)[
+  A B#box()#label Incomprehensibilities
+])))
+#latin(<free>)
+#latin(<limited>, justify-stretch: 0%)
+#latin(<ragged>, justify: false)
+#let cjk(label, ..args) = place(hide(block(width: 100pt, text(
+  lang: "zh",
+  font: "Noto Serif CJK SC",
+  par(..args)[中文维基百科使用汉字#box()#label 书写，汉字是汉族或华人的共同文字],
+))))
+#cjk(<cjk-free>)
+#cjk(<cjk-limited>, justify-stretch: 0%)
+#context {
+  let x(label) = locate(label).position().x
+  assert(x(<free>) > x(<ragged>))
+  test(x(<limited>), x(<ragged>))
+  test(x(<cjk-limited>), x(<cjk-free>))
+}
+
+--- justify-scale ---
+// Test that glyphs are scaled to fill a line, but only up to the limit.
+#set par(justify: true)
+#let word = [Typesetting]
+#let scaled(start, end, ..args) = context place(hide(block(
+  width: 1.02 * measure(word).width,
+  par(..args)[#box()#start#word#box()#end#linebreak(justify: true)],
+)))
+#scaled(<a>, <b>, justify-scale: 5%)
+#scaled(<c>, <d>, justify-scale: 1%)
+#context {
+  let x(label) = locate(label).position().x
+  let close(a, b) = calc.abs((a - b).pt()) < 0.01
+  let width = measure(word).width
+  assert(close(x(<b>) - x(<a>), 1.02 * width))
+  assert(close(x(<d>) - x(<c>), 1.01 * width))
+}
+
+--- justify-scale-invalid ---
+// Error: 25-29 justify scale must be at least 0% and less than 100%
+#set par(justify-scale: 100%)
+
+--- justify-glyphs ---
+// Test that space is spread between the characters of a justified line
+// without word spaces, such that the line is filled. The last character keeps
+// its natural width.
+#set par(justify: true, justify-last: true, justify-glyphs: true)
+#place(hide(block(width: 100pt)[#box()<start>TYPESETTIN#box()<end>G]))
+#context {
+  let x(label) = locate(label).position().x
+  let close(a, b) = calc.abs((a - b).pt()) < 0.01
+  assert(close(x(<end>) - x(<start>) + measure[G].width, 100pt))
+}
+
+--- justify-weighted ---
+// Test that the space beyond the stretch limit is distributed in proportion
+// to the width of the spaces, such that a sentence space stays twice as wide
+// as a word space.
+#set par(justify: true, justify-last: true, sentence-spacing: 200%)
+#place(hide(block(width: 200pt)[Fi#box()<a>rst. Se#box()<b>cond wo#box()<c>rd]))
+#context {
+  let x(label) = locate(label).position().x
+  let close(a, b) = calc.abs((a - b).pt()) < 0.01
+  let sentence = x(<b>) - x(<a>) - measure[rst.].width - measure[Se].width
+  let word = x(<c>) - x(<b>) - measure[cond].width - measure[wo].width
+  assert(close(sentence, 2 * word))
+}
+
+--- justify-kashida ---
+// Test that kashidas elongate the words of a justified line and that the line
+// is filled with and without them.
+#set text(lang: "ar", font: "Noto Sans Arabic")
+#set par(justify: true, justify-last: true)
+#place(hide(block(width: 100pt, par[
+  #box()<plain-start>سلام #box()<plain-mid>عليكم#box()<plain-end>
+])))
+#place(hide(block(width: 100pt, par(justify-kashida: true)[
+  #box()<kashida-start>سلام #box()<kashida-mid>عليكم#box()<kashida-end>
+])))
+#context {
+  let x(label) = locate(label).position().x
+  let close(a, b) = calc.abs((a - b).pt()) < 0.01
+  assert(close(x(<plain-start>) - x(<plain-end>), 100pt))
+  assert(close(x(<kashida-start>) - x(<kashida-end>), 100pt))
+  assert(x(<kashida-mid>) - x(<kashida-end>) > x(<plain-mid>) - x(<plain-end>))
+}
+
 --- justify-avoid-runts ---
 // Test that runts are avoided when it's not too costly to do so.
 #set page(width: 124pt)
@@ -8,6 +8,45 @@ This is a spaceexceedinglylongy.
 // Test two overlong words in a row.
 Supercalifragilisticexpialidocious Expialigoricmetrioxidation.
 
+--- linebreak-emergency ---
+// Test that overlong words are broken as a last resort.
+#let word = "Pneumonoultramicroscopicsilicovolcanoconiosis"
+#context {
+  let height(breaks) = measure(width: 50pt, par(emergency-breaks: breaks, word)).height
+  test(height(false), measure(word).height)
+  assert(height(true) > 2 * measure(word).height)
+}
+
+--- linebreak-balanced ---
+// Test that balanced breaking keeps the line count but evens out the lines.
+#context {
+  let body = [This caption is just long enough to wrap onto a second line.]
+  let simple = measure(par(linebreaks: "simple", body), width: 150pt)
+  let balanced = measure(par(linebreaks: "balanced", body), width: 150pt)
+  test(balanced.height, simple.height)
+  assert(balanced.width < simple.width)
+}
+
+--- linebreak-nobreak ---
+// Test that text can be protected from line breaks.
+#context {
+  let single = measure[Fig. 3].height
+  let kept = measure(block(width: 10pt, text(nobreak: true)[Fig. 3]))
+  assert(kept.height == single)
+}
+
+--- linebreak-zero-width-space ---
+// Test that a zero-width space allows a break without adding width.
+#context {
+  let hinted = "aaaaaaaa\u{200B}bbbbbbbb"
+  let plain = "aaaaaaaabbbbbbbb"
+  test(measure(hinted).width, measure(plain).width)
+  let width = measure("aaaaaaaa").width + 2pt
+  let kept = measure(block(width: width, plain)).height
+  let broken = measure(block(width: width, hinted)).height
+  assert(broken > kept)
+}
+
 --- linebreak-hyphen-nbsp ---
 // Test for non-breaking space and hyphen.
 There are non\u{2011}breaking~characters.
@@ -93,6 +132,92 @@ http://mydataurl/hash/12098541029831025981024980124124214/incremental/progress%l
 
 For info see #link("https://myhost.tld").
 
+--- linebreak-link-hyphenate ---
+// Test that links and file paths are broken at delimiters only and aren't
+// hyphenated.
+#context {
+  for body in (
+    "https://example.com/documentation/typesetting here",
+    "/usr/local/share/documentation/typesetting here",
+  ) {
+    let width = measure(body).width / 3
+    let hyphenated = block(width: width, text(lang: "en", hyphenate: true, body))
+    let plain = block(width: width, text(lang: "en", hyphenate: false, body))
+    test(measure(hyphenated), measure(plain))
+  }
+}
+
+--- linebreak-link-hyphenate-slash ---
+// Test that words joined by a slash aren't mistaken for file paths and are
+// still hyphenated.
+#context {
+  for body in ("typesetting/documentation", "hyphenation/typesetting") {
+    let narrow(hyphenate) = block(width: 1pt, text(lang: "en", hyphenate: hyphenate, body))
+    assert(measure(narrow(true)).height > measure(narrow(false)).height)
+  }
+}
+
+--- linebreak-link-grapheme-clusters ---
+// Test that breaking a long link at every character doesn't split its
+// grapheme clusters.
+#context {
+  for body in (
+    "https://a.co/" + "👨‍👩‍👧‍👦" * 20,
+    "https://a.co/" + "क्षत्रिय" * 5,
+  ) {
+    let narrow = text(top-edge: 1em, bottom-edge: 0em, par(leading: 0pt, body))
+    let lines = measure(block(width: 1pt, narrow)).height / text.size
+    assert(lines <= body.clusters().len())
+  }
+}
+
+--- linebreak-keep-trailing-spaces ---
+// Test that kept trailing spaces take up room at the end of a line.
+#context {
+  let body = [#box(width: 55pt) A]
+  let trimmed = measure(width: 60pt, par(body)).width
+  let kept = measure(width: 60pt, par(keep-trailing-spaces: true, body)).width
+  test(trimmed, 55pt)
+  assert(kept > trimmed)
+}
+
+--- linebreak-consecutive-height ---
+// Test that each of several consecutive line breaks produces an empty line
+// as high as a line of text in the line break's size.
+#context {
+  let height(body) = measure(body).height
+  let two = linebreak() + linebreak()
+  test(
+    height[A#two#linebreak()B] - height[A#two;B],
+    height[A#linebreak()A#linebreak()B] - height[A#linebreak()B],
+  )
+  test(
+    height[A#text(20pt, two)B] - height[A#text(20pt, linebreak())B],
+    height[A#linebreak()#text(20pt)[A]#linebreak()B] - height[A#linebreak()B],
+  )
+}
+
+--- linebreak-nobreak-punct ---
+// Test that punctuation stays with its word even across a space.
+#context {
+  let height(body) = measure(width: 1pt, body).height
+  test(height[A %], height[A])
+  assert(height[A B] > height[A])
+  test(height(par(nobreak-before: ("B",))[A B]), height[A])
+  test(height(par(nobreak-after: ("A",))[A B]), height[A])
+}
+
+--- linebreak-identifiers ---
+// Test that identifiers can be broken at their inner boundaries.
+#context {
+  let height(body) = measure(width: 1pt, body).height
+  let broken(body) = height(par(break-identifiers: true, body))
+  test(broken[fooBar], height[foo\ Bar])
+  test(broken[foo_bar], height[foo\ bar])
+  test(broken[McDonald], height[McDonald])
+  test(broken[\_init], height[\_init])
+}
+
 --- issue-2105-linebreak-tofu ---
 #linebreak()中文
 
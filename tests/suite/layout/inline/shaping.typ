@@ -60,6 +60,20 @@ A🐈ዲሞB
 // This should be a 1 in a box.
 1️⃣
 
+--- shaping-default-ignorables ---
+// Test that joiners and variation selectors stay attached to the characters
+// around them when lines are broken.
+#set text(font: ("Libertinus Serif", "Noto Sans Arabic"))
+#context {
+  let width(body) = measure(body).width
+  let broken(body) = measure(width: 1pt, body).width
+  test(broken[می\u{200c}خواهم], width[می\u{200c}خواهم])
+  test(
+    broken(par(emergency-breaks: true)[❤\u{fe0f}❤\u{fe0f}]),
+    width[❤\u{fe0f}],
+  )
+}
+
 --- shaping-emoji-bad-zwj ---
 // These two shouldn't be affected by a zero-width joiner.
 🏞‍🌋
@@ -67,6 +67,15 @@ I saw Zoe yӛsterday, on the tram.
 // Test tracking for only part of paragraph.
 I'm in#text(tracking: 0.15em + 1.5pt)[ spaace]!
 
+--- text-tracking-between-runs ---
+// Test that tracking continues across differently styled runs.
+#set text(tracking: 5pt)
+#context {
+  let plain = measure[abc].width
+  let styled = measure[a#text(fill: red)[b]c].width
+  assert(calc.abs((plain - styled).pt()) < 1)
+}
+
 --- text-tracking-mark-placement ---
 // Test that tracking doesn't disrupt mark placement.
 #set text(font: ("PT Sans", "Noto Serif Hebrew"))
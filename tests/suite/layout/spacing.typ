@@ -46,6 +46,29 @@ Totally #h() ignored
 #set align(right)
 Hello #h(2cm, weak: true)
 
+--- spacing-h-stretch ---
+// Test that stretchable spacing absorbs the slack of a justified line, but
+// only as far as it may stretch.
+#set par(justify: true)
+#place(hide(block(width: 100pt)[
+  #box[A]<a>#h(0pt, stretch: 200pt)#box[B]<b>#linebreak(justify: true)
+  #box[A]<c>#h(0pt, stretch: 10pt)#box[B]<d>#linebreak(justify: true)
+]))
+
+#context {
+  let x(label) = locate(label).position().x
+  let close(a, b) = calc.abs((a - b).pt()) < 0.01
+  assert(close(x(<b>) - x(<a>), 100pt - measure[B].width))
+  assert(close(x(<d>) - x(<c>), measure[A].width + 10pt))
+}
+
+--- spacing-h-stretch-weak ---
+// Test that weak stretchable spacing is trimmed at the start of a line.
+#context test(
+  measure(par[#h(1cm, stretch: 1cm, weak: true)Hello]).width,
+  measure[Hello].width,
+)
+
 --- issue-4087 ---
 // Weak space at the end of the line is removed.
 This is the first line #h(2cm, weak: true) A new line
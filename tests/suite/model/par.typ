@@ -26,6 +26,43 @@ But, soft! what light through yonder window breaks?
 
 It is the east, and Juliet is the sun.
 
+--- par-leading-ratio ---
+// Test leading relative to the height of the line above.
+#set par(leading-ratio: 100%)
+#context {
+  let line = measure[A].height
+  let height = measure[A \ B].height
+  assert(calc.abs((height - 3 * line).pt()) < 0.01)
+}
+
+--- par-leading-adaptive ---
+// Test leading that scales with the text size of the line below.
+#set par(leading: 5pt)
+#context {
+  let body = [A \ #text(2em)[B]]
+  let fixed = measure(body).height
+  let adaptive = measure(par(leading-adaptive: true, body)).height
+  assert(calc.abs((adaptive - fixed - 5pt).pt()) < 0.01)
+}
+
+--- par-empty-height ---
+// Test that paragraphs without any text still take up a line.
+#context {
+  let line = measure[A].height
+  test(measure(par[#h(1cm)]).height, line)
+  test(measure(par[]).height, line)
+}
+
+--- par-first-baseline ---
+// Test placing the first baseline at a fixed distance from the top.
+#set text(bottom-edge: "baseline")
+#context {
+  for size in (8pt, 14pt) {
+    let height = measure(par(first-baseline: 20pt, text(size)[A])).height
+    assert(calc.abs((height - 20pt).pt()) < 0.01)
+  }
+}
+
 --- par-spacing-context ---
 #set par(spacing: 10pt)
 #context test(par.spacing, 10pt)
@@ -79,6 +116,249 @@ Welcome \ here. Does this work well?
 لآن وقد أظلم الليل وبدأت النجوم
 تنضخ وجه الطبيعة التي أعْيَتْ من طول ما انبعثت في النهار
 
+--- par-max-lines ---
+// Test that paragraphs are clamped to a maximum number of lines.
+#set par(leading: 5pt, max-lines: 2)
+#context {
+  let line = measure(block(width: 100pt)[A]).height
+  let clamped = measure(block(width: 100pt, lorem(30))).height
+  assert(calc.abs((clamped - 2 * line - 5pt).pt()) < 0.01)
+}
+
+--- par-max-lines-zero ---
+// Error: 21-22 number must be positive
+#set par(max-lines: 0)
+
+--- par-max-lines-tags ---
+// Test that elements in the cut off text can still be located.
+#place(hide(par(max-lines: 1)[#lorem(30) #metadata(none)<end>]))
+#context test(query(<end>).len(), 1)
+
+--- par-max-lines-ellipsis ---
+// Test that the ellipsis of a clamped line is placed at the line's end, also
+// in right-to-left paragraphs and when the line doesn't end with text.
+#set text(font: ("Libertinus Serif", "Noto Sans Arabic"))
+#context {
+  let ellipsis = measure[…].width
+  let width(dir, body) = measure({
+    set text(dir: dir)
+    body
+  }).width
+  let clamped(dir, body) = measure(width: 60pt, {
+    set text(dir: dir)
+    par(max-lines: 1, body)
+  }).width
+  let approx(a, b) = assert(calc.abs((a - b).pt()) < 0.01)
+  approx(
+    clamped(ltr)[ab #box(width: 25pt) #box(width: 25pt)],
+    width(ltr)[ab #box(width: 25pt)] + ellipsis,
+  )
+  approx(
+    clamped(rtl)[اب #box(width: 25pt) #box(width: 25pt)],
+    width(rtl)[اب #box(width: 25pt)] + ellipsis,
+  )
+}
+
+--- par-overflow-clip ---
+// Test that clipping an overlong line keeps the size of the paragraph.
+#context {
+  let body = [Pneumonoultramicroscopicsilicovolcanoconiosis gypsy]
+  let size(overflow) = measure(block(width: 50pt, par(overflow: overflow, body)))
+  test(size("clip"), size("visible"))
+}
+
+--- par-overflow-invalid ---
+// Error: 20-21 expected "visible", "clip", or "truncate", found integer
+#set par(overflow: 1)
+
+--- par-first-line ---
+// Test that only the first line is restyled.
+#context {
+  let body = [A \ B \ C]
+  let normal = measure(block(width: 100pt, par(body))).height
+  let large = measure(block(width: 100pt, par(first-line: arguments(size: 2em), body)))
+  let single = measure(block(width: 100pt, par(first-line: arguments(size: 2em))[A]))
+  let line = measure(block(width: 100pt)[A]).height
+  assert(calc.abs((large.height - normal - single.height + line).pt()) < 0.01)
+}
+
+--- par-first-line-overfull ---
+// Test that the first line is measured with its additional styles.
+#set par(overfull-tolerance: 1pt)
+// Warning: 86-89 overfull line (20pt too wide)
+// Hint: 86-89 try hyphenating or rephrasing the line
+#context block(width: measure[AAA].width, par(first-line: arguments(tracking: 10pt))[AAA])
+
+--- par-first-line-invalid ---
+// Error: 28-48 unexpected argument: something
+#par(first-line: arguments(something: "invalid"))[A]
+
+--- par-width ---
+#context test(measure(par(width: 60pt, lorem(20))).width, 60pt)
+
+--- par-hyphen-invalid ---
+// Error: 18-22 expected exactly one character
+#set par(hyphen: "ab")
+
+--- par-last-line-align ---
+// Test that only the last line of a justified paragraph is aligned
+// differently.
+#set par(justify: true, last-line-align: right)
+#place(hide(block(width: 100pt)[
+  #box[A]<first> #lorem(10) #box[Z]<last>
+]))
+
+#context {
+  let x(label) = locate(label).position().x
+  let close(a, b) = calc.abs((a - b).pt()) < 0.01
+  assert(close(x(<last>) + measure[Z].width - x(<first>), 100pt))
+  assert(locate(<first>).position().y < locate(<last>).position().y)
+}
+
+--- par-tolerance-negative ---
+// Error: 21-23 tolerance must be a non-negative number
+#set par(tolerance: -1)
+
+--- par-tolerance-nan ---
+// Error: 21-30 tolerance must be a non-negative number
+#set par(tolerance: float.nan)
+
+--- par-underfull-warning ---
+#set text(size: 10pt)
+#set par(justify: true, tolerance: 100)
+// Warning: 22-38 underfull line (badness 2700)
+// Hint: 22-38 try hyphenating or rephrasing the line
+#block(width: 20pt)[#box(width: 10pt)#linebreak(justify: true)#box(width: 10pt)]
+
+--- par-overfull-warning ---
+#set par(overfull-tolerance: 1pt)
+// Warning: 21-37 overfull line (10pt too wide)
+// Hint: 21-37 try hyphenating or rephrasing the line
+#block(width: 20pt, box(width: 30pt))
+
+--- par-baseline-grid ---
+// Test that the baseline of every line lands on the grid, also for the lines
+// that are kept together to prevent orphans and widows.
+#let lines = range(5).map(i => [Line#metadata(i)<grid>]).join(linebreak())
+#place(hide(block(par(baseline-grid: 20pt, lines))))
+#context {
+  let ys = query(<grid>).map(it => it.location().position().y)
+  for (i, y) in ys.enumerate() {
+    assert(calc.abs((y - ys.first() - i * 20pt).pt()) < 0.01)
+  }
+}
+
+--- par-line-numbering ---
+// Test that every line is numbered next to its own baseline, also the lines
+// that are kept together to prevent orphans and widows.
+#let lines = range(5).map(i => [Line#metadata(i)<line>]).join(linebreak())
+#place(hide(block(par(line-numbering: n => [#metadata(n)<number>], lines))))
+#context {
+  let numbers = query(<number>)
+  test(numbers.map(it => it.value), range(1, 6))
+  let ys(label) = {
+    let ys = query(label).map(it => it.location().position().y)
+    ys.map(y => y - ys.first())
+  }
+  for (a, b) in ys(<number>).zip(ys(<line>)) {
+    assert(calc.abs((a - b).pt()) < 0.01)
+  }
+}
+
+--- par-justify-vertical ---
+// Test that the leading between all lines of a finished column is stretched
+// evenly, also between the lines that are kept together to prevent orphans.
+#let lines = range(12).map(i => [Line#metadata(i)<v>]).join(linebreak())
+#place(hide(block(height: 100pt, columns(2, par(justify-vertical: 100%, lines)))))
+#context {
+  let positions = query(<v>).map(it => it.location().position())
+  let ys = positions.filter(p => p.x == positions.first().x).map(p => p.y)
+  let gaps = range(1, ys.len()).map(i => ys.at(i) - ys.at(i - 1))
+  let natural = (measure(lines).height - measure[Line].height) / 11
+  assert(gaps.len() > 2)
+  for gap in gaps {
+    assert(calc.abs((gap - gaps.first()).pt()) < 0.01)
+    assert(gap > natural + 0.01pt)
+  }
+}
+
+--- par-keep-together ---
+// Test that a paragraph that is kept together moves to the next region as a
+// whole, unless it is too tall to fit into any region.
+#let lines(n, label) = range(n).map(i => [Line#metadata(i)#label]).join(linebreak())
+#let sample(body, ..args) = place(hide(block(height: 100pt, columns(2)[
+  #v(50pt)
+  #par(..args, body)
+])))
+#sample(lines(5, <split>))
+#sample(lines(5, <kept>), keep-together: true)
+#sample(lines(12, <tall>), keep-together: true)
+#context {
+  let xs(label) = query(label).map(it => it.location().position().x).dedup()
+  test(xs(<split>).len(), 2)
+  test(xs(<kept>), (xs(<split>).last(),))
+  test(xs(<tall>).len(), 2)
+}
+
+--- par-keep-together-invalid ---
+// Error: 25-30 expected boolean or integer, found string
+#set par(keep-together: "yes")
+
+--- par-keep-with-next ---
+// Test that a paragraph moves to the next region along with the first lines of
+// the paragraph that follows it.
+#let sample(label, ..args) = place(hide(block(height: 60pt, columns(2)[
+  #v(40pt)
+  #par(..args)[Head#metadata(none)#label]
+
+  #lorem(8)
+])))
+#sample(<alone>)
+#sample(<kept>, keep-with-next: true)
+#context assert(locate(<kept>).position().x > locate(<alone>).position().x)
+
+--- par-long-parallel ---
+// Test that the lines of long paragraphs, which are built in parallel, are
+// stacked and located just like those of short ones.
+#set par(leading: 5pt)
+#let lines(n) = range(n).map(i => [#i#metadata(i)<line>]).join(linebreak())
+#place(hide(block(width: 100pt, lines(40))))
+#context {
+  let height(n) = measure(block(width: 100pt, lines(n))).height
+  for n in (10, 40) {
+    let expected = n * height(1) + (n - 1) * 5pt
+    assert(calc.abs((height(n) - expected).pt()) < 0.01)
+  }
+  let found = query(<line>)
+  test(found.map(it => it.value), range(40))
+  let ys = found.map(it => it.location().position().y)
+  test(ys, ys.sorted())
+}
+
+--- par-sentence-spacing ---
+// Test that only spaces between sentences are widened.
+#context {
+  let wide(body) = measure(par(sentence-spacing: 200%, body)).width
+  test(wide[First. Second], measure(text(spacing: 200%)[First. Second]).width)
+  test(wide[Really? Yes], measure(text(spacing: 200%)[Really? Yes]).width)
+  test(wide[Dr. Who], measure[Dr. Who].width)
+  test(wide[First. second], measure[First. second].width)
+}
+
+--- par-sentence-spacing-context ---
+// Test that short words still end sentences, that abbreviations depend on the
+// language, and that sentences are detected across differently styled text.
+#context {
+  let wide(body) = measure(par(sentence-spacing: 200%, body)).width
+  let spaced(body) = measure(text(spacing: 200%, body)).width
+  for body in ([Yes. No], [Bob. He], [I. Then], [*End.* Next], [End. _Next_]) {
+    test(wide(body), spaced(body))
+  }
+  test(wide(text(lang: "de")[Dr. Who]), measure(text(lang: "de")[Dr. Who]).width)
+  test(wide(text(lang: "de")[z.B. Rot]), measure(text(lang: "de")[z.B. Rot]).width)
+  test(wide(text(lang: "de")[Mr. Who]), spaced(text(lang: "de")[Mr. Who]))
+}
+
 --- par-trailing-whitespace ---
 // Ensure that trailing whitespace layouts as intended.
 #box(fill: aqua, " ")
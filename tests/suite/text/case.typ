@@ -5,7 +5,23 @@
 #test(lower(memes), "are memes great?")
 #test(upper(memes), "ARE MEMES GREAT?")
 #test(upper("Ελλάδα"), "ΕΛΛΆΔΑ")
+#test(upper("Straße"), "STRASSE")
 
 --- upper-bad-type ---
 // Error: 8-9 expected string or content, found integer
 #upper(1)
+
+--- text-case-width ---
+// Test that the case property transforms text before it is laid out.
+#context test(
+  measure(text(case: "upper")[hello world]).width,
+  measure[HELLO WORLD].width,
+)
+#context test(
+  measure(text(case: "title")[hello world]).width,
+  measure[Hello World].width,
+)
+#context test(
+  measure(text(lang: "tr", case: "upper")[istanbul]).width,
+  measure(text(lang: "tr")[İSTANBUL]).width,
+)
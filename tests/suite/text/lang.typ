@@ -49,6 +49,23 @@
   [Ş ]
 }
 
+--- text-dir-from-script ---
+// Test that the direction is inferred from the script if it is set and from
+// the language otherwise.
+#let probe(label, ..args) = place(hide(block(width: 100pt, text(..args)[#box[A]#label])))
+#probe(<hebr>, script: "hebr")
+#probe(<syrc>, script: "syrc", lang: "en")
+#probe(<latn>, script: "latn", lang: "ar")
+#probe(<ar>, lang: "ar")
+#context {
+  let x(label) = locate(label).position().x - locate(<latn>).position().x
+  let close(a, b) = calc.abs((a - b).pt()) < 0.01
+  let end = 100pt - measure[A].width
+  assert(close(x(<hebr>), end))
+  assert(close(x(<syrc>), end))
+  assert(close(x(<ar>), end))
+}
+
 --- text-script-bad-type ---
 // Error: 19-23 expected string or auto, found none
 #set text(script: none)
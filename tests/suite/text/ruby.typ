@@ -0,0 +1,20 @@
+// Test ruby annotations.
+
+--- ruby-size ---
+// The annotation adds to the height, but a narrow one not to the width.
+#context {
+  let base = measure[Base]
+  let annotated = measure(ruby[Base][b])
+  test(annotated.width, base.width)
+  assert(annotated.height > base.height)
+}
+
+--- ruby-overhang ---
+// A wide annotation may only overhang its base if enabled.
+#context {
+  let base = measure[a]
+  let wide = measure(ruby(overhang: false)[a][annotation])
+  let overhanging = measure(ruby[a][annotation])
+  assert(wide.width > base.width)
+  assert(overhanging.width < wide.width)
+}
@@ -10,3 +10,11 @@
 
 #show smallcaps: set text(fill: red)
 #smallcaps[Smallcaps]
+
+--- smallcaps-all ---
+// Test that uppercase letters only become small capitals if requested.
+#context {
+  let width = measure[UNICEF].width
+  test(measure(smallcaps[UNICEF]).width, width)
+  assert(measure(smallcaps(all: true)[UNICEF]).width < width)
+}
@@ -58,3 +58,17 @@ LLLLLLLLLLLLLLLLLL R _L_
 // Test that thin spaces are preserved.
 | | U+0020 regular space \
 | | U+2009 thin space
+
+--- space-fixed-unbreakable ---
+// Test that fixed-width spaces don't offer a line break opportunity and scale
+// with the text size.
+#context {
+  let single = measure[AB].height
+  assert(measure(width: 1pt)[A B].height > single)
+  for space in (sym.space.en, sym.space.quad, sym.space.thin, sym.space.hair) {
+    let body = [A#space;B]
+    test(measure(width: 1pt, body).height, single)
+    let delta = measure(text(size: 20pt, body)).width - 2 * measure(body).width
+    assert(calc.abs(delta.pt()) < 0.01)
+  }
+}